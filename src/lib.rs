@@ -1,24 +1,190 @@
-use anyhow::Result;
+use cargo_metadata::camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::{
     DepKindInfo, DependencyKind, Metadata, MetadataCommand, Node, NodeDep, Package, PackageId,
 };
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use indicatif::{ProgressBar, ProgressStyle};
 use itertools::Itertools;
+use schemars::JsonSchema;
 use semver::Version;
-use serde_derive::Serialize;
-use std::collections::{HashMap, HashSet};
-use std::io;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Errors returned by the library functions.
+#[derive(Debug, thiserror::Error)]
+pub enum CargoLicenseError {
+    #[error("failed to run `cargo metadata`")]
+    Metadata(#[from] cargo_metadata::Error),
+    #[error(
+        "Cargo.lock is missing or out of date, and `--locked` or `--frozen` forbids updating it"
+    )]
+    LockfileUpdate(#[source] cargo_metadata::Error),
+    #[error("failed to parse license expression `{expression}`")]
+    Spdx {
+        expression: String,
+        #[source]
+        source: spdx::ParseError,
+    },
+    #[error("invalid license `{license}` in policy")]
+    PolicyLicense {
+        license: String,
+        #[source]
+        source: spdx::ParseError,
+    },
+    #[error("invalid excluded license `{license}`")]
+    ExcludedLicense {
+        license: String,
+        #[source]
+        source: spdx::ParseError,
+    },
+    #[error("invalid project license `{license}`")]
+    ProjectLicense {
+        license: String,
+        #[source]
+        source: spdx::ParseError,
+    },
+    #[error("invalid crate `{spec}` in policy")]
+    PolicyCrate {
+        spec: String,
+        #[source]
+        source: semver::Error,
+    },
+    #[error("package `{0}` is not a member of the workspace")]
+    UnknownPackage(String),
+    #[error("invalid exclude pattern")]
+    Glob(#[from] globset::Error),
+    #[error("failed to parse configuration")]
+    Config(#[from] toml::de::Error),
+    #[error("failed to write TSV output")]
+    Tsv(#[from] csv::Error),
+    #[error("failed to write TOML output")]
+    Toml(#[from] toml::ser::Error),
+    #[error("failed to write JSON output")]
+    Json(#[from] serde_json::Error),
+    #[error("failed to render template")]
+    Template(#[from] handlebars::RenderError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+pub type Result<T, E = CargoLicenseError> = std::result::Result<T, E>;
 
 fn normalize(license_string: &str) -> String {
-    let mut list: Vec<&str> = license_string
+    normalize_with(license_string, true)
+}
+
+/// Like [`normalize`], but keeps the `OR` terms in their original order
+/// unless `sort` is set.
+fn normalize_with(license_string: &str, sort: bool) -> String {
+    let terms = license_string
         .split('/')
         .flat_map(|e| e.split(" OR "))
-        .map(str::trim)
-        .collect();
-    list.sort_unstable();
-    list.dedup();
+        .map(|term| {
+            // Splitting on `AND` would cut through the parentheses of nested
+            // terms, so those are left as they are, like in `absorb`.
+            if term.contains(['(', ')']) {
+                return term.trim().to_owned();
+            }
+            // Collapse repeated requirements, keeping their order.
+            let mut requirements = Vec::new();
+            for requirement in term.split(" AND ").map(str::trim) {
+                if !requirements.contains(&requirement) {
+                    requirements.push(requirement);
+                }
+            }
+            requirements.join(" AND ")
+        });
+    let mut list: Vec<String> = Vec::new();
+    for term in terms {
+        if !list.contains(&term) {
+            list.push(term);
+        }
+    }
+    if sort {
+        list.sort_unstable();
+    }
+    absorb(&mut list);
     list.join(" OR ")
 }
 
+/// Applies the absorption law `A OR (A AND B) = A` to the `OR` terms of a
+/// license expression, dropping every term whose requirements are a
+/// superset of another term's.
+///
+/// This is only done when every term is a plain `AND` list, optionally
+/// wrapped in a single pair of parentheses. Expressions with any other
+/// nesting are left untouched, since splitting them on `OR` does not yield
+/// their actual terms.
+fn absorb(terms: &mut Vec<String>) {
+    let requirements = terms
+        .iter()
+        .map(|term| {
+            let inner = term
+                .strip_prefix('(')
+                .and_then(|term| term.strip_suffix(')'))
+                .unwrap_or(term);
+            if inner.contains(['(', ')']) {
+                return None;
+            }
+            Some(inner.split(" AND ").map(str::trim).collect::<HashSet<_>>())
+        })
+        .collect::<Option<Vec<_>>>();
+    let Some(requirements) = requirements else {
+        return;
+    };
+    let absorbed = requirements
+        .iter()
+        .enumerate()
+        .map(|(index, term)| {
+            requirements.iter().enumerate().any(|(other_index, other)| {
+                other_index != index
+                    && other.is_subset(term)
+                    && (other != term || other_index < index)
+            })
+        })
+        .collect::<Vec<_>>();
+    let mut absorbed = absorbed.into_iter();
+    terms.retain(|_| !absorbed.next().unwrap_or(false));
+}
+
+/// Returns `true` if `license_string` mixes `AND` and `OR` (or `/`) at the
+/// same nesting level, relying on SPDX's implicit precedence where `AND`
+/// binds tighter than `OR`.
+fn has_ambiguous_precedence(license_string: &str) -> bool {
+    let spaced = license_string
+        .replace('(', " ( ")
+        .replace(')', " ) ")
+        .replace('/', " OR ");
+    // `(seen AND, seen OR)` for every open nesting level.
+    let mut levels = vec![(false, false)];
+    for token in spaced.split_whitespace() {
+        match token {
+            "(" => levels.push((false, false)),
+            ")" if levels.len() > 1 => {
+                levels.pop();
+            }
+            "AND" | "OR" => {
+                let level = levels.last_mut().expect("at least one level");
+                if token == "AND" {
+                    level.0 = true;
+                } else {
+                    level.1 = true;
+                }
+                if level.0 && level.1 {
+                    return true;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
 fn get_proc_macro_node_names(metadata: &Metadata, opt: &GetDependenciesOpt) -> HashSet<String> {
     let mut proc_macros = HashSet::new();
     if opt.avoid_proc_macros {
@@ -36,14 +202,36 @@ fn get_proc_macro_node_names(metadata: &Metadata, opt: &GetDependenciesOpt) -> H
     proc_macros
 }
 
-fn get_node_name_filter(metadata: &Metadata, opt: &GetDependenciesOpt) -> HashSet<String> {
-    let mut filter = HashSet::new();
+fn get_roots<'a>(metadata: &'a Metadata, opt: &GetDependenciesOpt) -> Result<Vec<&'a Package>> {
+    if let Some(name) = &opt.package {
+        return metadata
+            .workspace_packages()
+            .into_iter()
+            .find(|package| package.name == *name)
+            .map(|package| vec![package])
+            .ok_or_else(|| CargoLicenseError::UnknownPackage(name.clone()));
+    }
+    let members = metadata.workspace_packages();
+    if let Some(name) = opt
+        .exclude_workspace_members
+        .iter()
+        .find(|name| !members.iter().any(|member| member.name == **name))
+    {
+        return Err(CargoLicenseError::UnknownPackage(name.clone()));
+    }
+    let excluded = |package: &Package| opt.exclude_workspace_members.contains(&package.name);
+    // Without the root package, every other member is a root.
+    Ok(match metadata.root_package() {
+        Some(root) if !excluded(root) => vec![root],
+        _ => members
+            .into_iter()
+            .filter(|member| !excluded(member))
+            .collect(),
+    })
+}
 
-    let roots = if let Some(root) = metadata.root_package() {
-        vec![root]
-    } else {
-        metadata.workspace_packages()
-    };
+fn get_node_name_filter(roots: &[&Package], opt: &GetDependenciesOpt) -> HashSet<String> {
+    let mut filter = HashSet::new();
 
     if opt.root_only {
         for root in roots {
@@ -55,15 +243,106 @@ fn get_node_name_filter(metadata: &Metadata, opt: &GetDependenciesOpt) -> HashSe
     if opt.direct_deps_only {
         for root in roots {
             filter.insert(root.name.clone());
-            for package in root.dependencies.iter() {
-                filter.insert(package.name.clone());
-            }
         }
+        filter.extend(get_direct_dependency_names(roots));
     }
     filter
 }
 
-#[derive(Debug, Serialize, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+/// Ranks the packages of a dependency graph so that every package comes
+/// before its dependencies, using Kahn's algorithm. Ties are broken by name
+/// and version; packages in a cycle are left unranked.
+fn topological_ranks<'a>(
+    metadata: &'a Metadata,
+    edges: &HashMap<&'a PackageId, Vec<&'a PackageId>>,
+) -> HashMap<&'a PackageId, usize> {
+    let packages = metadata
+        .packages
+        .iter()
+        .map(|p| (&p.id, p))
+        .collect::<HashMap<_, _>>();
+    let key = |id: &'a PackageId| (&packages[id].name, &packages[id].version, id);
+
+    let mut incoming = HashMap::<&PackageId, usize>::new();
+    for dependency in edges.values().flatten() {
+        *incoming.entry(dependency).or_default() += 1;
+    }
+    let mut ready = edges
+        .keys()
+        .filter(|package_id| !incoming.contains_key(*package_id))
+        .map(|package_id| key(package_id))
+        .collect::<BTreeSet<_>>();
+    let mut ranks = HashMap::new();
+    while let Some((_, _, package_id)) = ready.pop_first() {
+        ranks.insert(package_id, ranks.len());
+        for dependency in &edges[package_id] {
+            let count = incoming.get_mut(dependency).expect("counted above");
+            *count -= 1;
+            if *count == 0 {
+                ready.insert(key(dependency));
+            }
+        }
+    }
+    ranks
+}
+
+fn dependency_kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Development => "dev",
+        DependencyKind::Build => "build",
+        _ => "normal",
+    }
+}
+
+/// The dependency kinds through which every package is reachable from the
+/// roots. Dependencies of build and dev dependencies are reached through the
+/// same kind, whatever kind of dependency they are themselves.
+fn reached_kinds<'a>(
+    roots: &[&'a Package],
+    deps: &HashMap<&'a PackageId, &'a Vec<NodeDep>>,
+    connected: &HashSet<&'a PackageId>,
+    opt: &GetDependenciesOpt,
+) -> HashMap<&'a PackageId, BTreeSet<&'static str>> {
+    let mut reached = HashMap::<&PackageId, BTreeSet<&'static str>>::new();
+    let stack = &mut roots
+        .iter()
+        .map(|root| (&root.id, "normal"))
+        .collect::<Vec<_>>();
+    while let Some((package_id, kind)) = stack.pop() {
+        if !reached.entry(package_id).or_default().insert(kind) {
+            continue;
+        }
+        for NodeDep { pkg, dep_kinds, .. } in deps[package_id] {
+            if !connected.contains(pkg) {
+                continue;
+            }
+            // Cargo before 1.41 doesn't report dependency kinds.
+            if dep_kinds.is_empty() {
+                stack.push((pkg, kind));
+            }
+            for DepKindInfo { kind: dep_kind, .. } in dep_kinds {
+                if opt.includes_dep_kind(*dep_kind) {
+                    let dep_kind = dependency_kind_name(*dep_kind);
+                    stack.push((pkg, if dep_kind == "normal" { kind } else { dep_kind }));
+                }
+            }
+        }
+    }
+    reached
+}
+
+/// Names of the packages the roots directly depend on.
+fn get_direct_dependency_names(roots: &[&Package]) -> HashSet<String> {
+    roots
+        .iter()
+        .flat_map(|root| &root.dependencies)
+        .map(|dependency| dependency.name.clone())
+        .collect()
+}
+
+#[derive(
+    Debug, Serialize, Deserialize, JsonSchema, Clone, Hash, Ord, PartialOrd, Eq, PartialEq,
+)]
 pub struct DependencyDetails {
     pub name: String,
     pub version: semver::Version,
@@ -72,15 +351,374 @@ pub struct DependencyDetails {
     pub license: Option<String>,
     pub license_file: Option<String>,
     pub description: Option<String>,
+    /// Whether the resolved version is yanked from crates.io, only set when
+    /// checking for yanked versions and the version is in cargo's registry
+    /// index cache.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub yanked: Option<bool>,
+    /// Number of crates in the report depending on this one, only set when
+    /// graph statistics are requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependents: Option<usize>,
+    /// Whether a root depends on the crate directly, only set when the
+    /// transitive count is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub is_direct: Option<bool>,
+    /// The dependency kinds (`normal`, `build` or `dev`) through which the
+    /// crate is reachable, only set when kinds are requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kinds: Option<Vec<String>>,
+    /// The shortest chain of crates from a root to this one, as `name
+    /// version`, only set when dependency paths are requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dependency_path: Option<Vec<String>>,
+    /// Justification of an overridden license, from the overrides file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_reason: Option<String>,
+    /// Who approved an overridden license, from the overrides file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub override_approved_by: Option<String>,
+    /// Tags attached to the crate in the tags file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    /// SHA-256 of the `license-file`, only set when hashing license files.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub license_file_sha256: Option<String>,
+    /// Features enabled for the crate in the resolved graph, only set when
+    /// features are requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub features: Option<Vec<String>>,
+    /// Rust edition of the crate, only set when package metadata is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub edition: Option<String>,
+    /// Minimum supported Rust version declared by the crate, only set when
+    /// package metadata is requested.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rust_version: Option<String>,
+    /// Path to the crate's `Cargo.toml`, used to locate its license texts.
+    #[serde(skip)]
+    pub manifest_path: Utf8PathBuf,
+    /// Path to the crate's `license-file`, resolved against its manifest
+    /// directory regardless of how `license_file` is reported.
+    #[serde(skip)]
+    pub license_file_path: Option<Utf8PathBuf>,
+    /// Whether the crate comes from crates.io.
+    #[serde(skip)]
+    pub crates_io: bool,
+}
+
+/// An author parsed from the `Name <email>` format of `Cargo.toml`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Author<'a> {
+    name: Option<&'a str>,
+    email: Option<&'a str>,
+}
+
+impl<'a> Author<'a> {
+    fn parse(author: &'a str) -> Self {
+        let author = author.trim();
+        if let Some((name, email)) = author
+            .strip_suffix('>')
+            .and_then(|author| author.rsplit_once('<'))
+        {
+            let name = name.trim();
+            return Self {
+                name: (!name.is_empty()).then_some(name),
+                email: Some(email.trim()),
+            };
+        }
+        if author.contains('@') && !author.contains(char::is_whitespace) {
+            return Self {
+                name: None,
+                email: Some(author),
+            };
+        }
+        Self {
+            name: Some(author),
+            email: None,
+        }
+    }
+
+    /// Authors with the same email, or the same name if they have no email,
+    /// are the same person.
+    fn key(&self) -> String {
+        self.email.or(self.name).unwrap_or_default().to_lowercase()
+    }
+}
+
+impl fmt::Display for Author<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name, self.email) {
+            (Some(name), Some(email)) => write!(f, "{name} <{email}>"),
+            (Some(name), None) => f.write_str(name),
+            (None, Some(email)) => f.write_str(email),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+/// Separates the authors of a crate unless another separator is chosen.
+const DEFAULT_AUTHOR_SEPARATOR: &str = "|";
+
+/// Canonicalizes a `separator`-separated list of authors to `Name <email>`,
+/// `Name` or `email`, dropping duplicates of the same person. A duplicate
+/// with a name replaces an earlier one with only an email.
+#[must_use]
+pub fn normalize_authors(authors: &str, separator: &str) -> String {
+    let mut unique: Vec<Author<'_>> = Vec::new();
+    for author in authors.split(separator).map(Author::parse) {
+        if author.key().is_empty() {
+            continue;
+        }
+        match unique.iter_mut().find(|other| other.key() == author.key()) {
+            Some(other) if other.name.is_none() => *other = author,
+            Some(_) => {}
+            None => unique.push(author),
+        }
+    }
+    unique.iter().join(separator)
+}
+
+/// Canonicalizes a repository URL to `https://host/path`, converting
+/// `git@host:path`, `ssh://`, `git://` and `http://` URLs and dropping the
+/// `git+` prefix, `.git` suffix and trailing slashes.
+#[must_use]
+pub fn normalize_repository(url: &str) -> String {
+    let url = url.trim();
+    let url = url.strip_prefix("git+").unwrap_or(url);
+    let rest = if let Some(rest) = ["https://", "http://", "git://", "ssh://"]
+        .iter()
+        .find_map(|scheme| url.strip_prefix(scheme))
+    {
+        // Drop the user of `ssh://git@host/path`.
+        let (authority, path) = rest.split_once('/').unwrap_or((rest, ""));
+        let host = authority
+            .rsplit_once('@')
+            .map_or(authority, |(_, host)| host);
+        format!("{host}/{path}")
+    } else if let Some((host, path)) = url
+        .split_once('@')
+        .and_then(|(_, host_path)| host_path.split_once(':'))
+    {
+        format!("{host}/{path}")
+    } else {
+        return url.to_owned();
+    };
+    let rest = rest.trim_end_matches('/');
+    let rest = rest.strip_suffix(".git").unwrap_or(rest);
+    format!("https://{rest}")
+}
+
+/// A distinct author of the report along with the number of crates they
+/// are an author of.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct AuthorCount {
+    pub author: String,
+    pub crates: usize,
+}
+
+/// Counts the crates of every distinct author in `dependencies`, most
+/// prolific first. Authors are told apart as in [`normalize_authors`].
+#[must_use]
+pub fn author_counts(dependencies: &[DependencyDetails]) -> Vec<AuthorCount> {
+    let mut counts = HashMap::<String, (Author<'_>, usize)>::new();
+    for authors in dependencies.iter().filter_map(|d| d.authors.as_deref()) {
+        let mut seen = HashSet::new();
+        for author in authors.split('|').map(Author::parse) {
+            let key = author.key();
+            if key.is_empty() || !seen.insert(key.clone()) {
+                continue;
+            }
+            let (known, crates) = counts.entry(key).or_insert((author.clone(), 0));
+            if known.name.is_none() {
+                *known = author;
+            }
+            *crates += 1;
+        }
+    }
+    let mut counts = counts
+        .into_values()
+        .map(|(author, crates)| AuthorCount {
+            author: author.to_string(),
+            crates,
+        })
+        .collect::<Vec<_>>();
+    counts.sort_by(|a, b| {
+        b.crates
+            .cmp(&a.crates)
+            .then_with(|| a.author.cmp(&b.author))
+    });
+    counts
+}
+
+/// Differences between two reports, as produced by [`diff_dependencies`].
+#[derive(Debug, Clone, Default)]
+pub struct ReportDiff {
+    pub added: Vec<DependencyDetails>,
+    pub removed: Vec<DependencyDetails>,
+    /// Crates present in both reports with different details, as
+    /// `(old, new)` pairs.
+    pub changed: Vec<(DependencyDetails, DependencyDetails)>,
+}
+
+impl ReportDiff {
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Returns `true` if both crates are reported the same. The serialized form
+/// is compared so that fields not part of the output, such as
+/// `manifest_path`, are ignored.
+fn same_output(a: &DependencyDetails, b: &DependencyDetails) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Keeps the first crate per name and version of `dependencies`, in their
+/// order, warning about every dropped duplicate whose details differ from
+/// the kept one.
+///
+/// A crate that is direct in one report and transitive in another is kept
+/// as direct, and its dependency kinds are merged, since those only depend
+/// on where the crate was found.
+pub fn dedupe_dependencies(dependencies: &mut Vec<DependencyDetails>) -> Vec<Warning> {
+    let mut warnings = Vec::new();
+    let mut kept = HashMap::<_, usize>::new();
+    let mut deduped = Vec::<DependencyDetails>::with_capacity(dependencies.len());
+    for mut dependency in dependencies.drain(..) {
+        let key = (dependency.name.clone(), dependency.version.clone());
+        if let Some(&index) = kept.get(&key) {
+            let first = &mut deduped[index];
+            if let (Some(is_direct), Some(other)) = (&mut first.is_direct, dependency.is_direct) {
+                *is_direct |= other;
+            }
+            if let (Some(kinds), Some(other)) = (&mut first.kinds, &dependency.kinds) {
+                kinds.extend(other.iter().cloned());
+                kinds.sort_unstable();
+                kinds.dedup();
+            }
+            dependency.is_direct = first.is_direct;
+            dependency.kinds.clone_from(&first.kinds);
+            if !same_output(&dependency, first) {
+                warnings.push(Warning::new(
+                    WarningKind::DuplicateCrate,
+                    format!(
+                        "`{} {}` appears more than once with different details, keeping the first",
+                        dependency.name, dependency.version
+                    ),
+                ));
+            }
+        } else {
+            kept.insert(key, deduped.len());
+            deduped.push(dependency);
+        }
+    }
+    *dependencies = deduped;
+    warnings
+}
+
+/// Compares two reports. Crates are matched by name and version; a crate
+/// whose version changed is reported as changed when only one version of it
+/// is present on each side.
+#[must_use]
+pub fn diff_dependencies(old: &[DependencyDetails], new: &[DependencyDetails]) -> ReportDiff {
+    let key = |d: &DependencyDetails| (d.name.clone(), d.version.clone());
+    let old_by_key = old.iter().map(|d| (key(d), d)).collect::<BTreeMap<_, _>>();
+    let new_by_key = new.iter().map(|d| (key(d), d)).collect::<BTreeMap<_, _>>();
+
+    let mut diff = ReportDiff::default();
+    for (key, old) in &old_by_key {
+        if let Some(new) = new_by_key.get(key) {
+            if !same_output(old, new) {
+                diff.changed.push(((*old).clone(), (*new).clone()));
+            }
+        }
+    }
+    let removed = old_by_key
+        .iter()
+        .filter(|(key, _)| !new_by_key.contains_key(key))
+        .map(|(_, d)| *d)
+        .collect::<Vec<_>>();
+    let added = new_by_key
+        .iter()
+        .filter(|(key, _)| !old_by_key.contains_key(key))
+        .map(|(_, d)| *d)
+        .collect::<Vec<_>>();
+    let count =
+        |list: &[&DependencyDetails], name: &str| list.iter().filter(|d| d.name == name).count();
+    for old in &removed {
+        let unique = count(&removed, &old.name) == 1 && count(&added, &old.name) == 1;
+        match added.iter().find(|new| unique && new.name == old.name) {
+            Some(new) => diff.changed.push(((*old).clone(), (*new).clone())),
+            None => diff.removed.push((*old).clone()),
+        }
+    }
+    for new in &added {
+        let unique = count(&removed, &new.name) == 1 && count(&added, &new.name) == 1;
+        if !unique {
+            diff.added.push((*new).clone());
+        }
+    }
+    diff.changed.sort();
+    diff
+}
+
+/// Computes a SHA-256 hash of the name, version, license and license file of
+/// every dependency. The hash only depends on the set of dependencies, not
+/// on their order.
+#[must_use]
+pub fn license_hash(dependencies: &[DependencyDetails]) -> String {
+    let mut lines = dependencies
+        .iter()
+        .map(|d| {
+            format!(
+                "{}\t{}\t{}\t{}\n",
+                d.name,
+                d.version,
+                d.license.as_deref().unwrap_or_default(),
+                d.license_file.as_deref().unwrap_or_default()
+            )
+        })
+        .collect::<Vec<_>>();
+    lines.sort_unstable();
+    lines.dedup();
+    format!("{:x}", Sha256::digest(lines.concat()))
+}
+
+/// Reads a report previously written with [`write_json`].
+pub fn read_json(path: &Path) -> Result<Vec<DependencyDetails>> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// A license text file shipped with a crate.
+#[derive(Debug, Clone)]
+pub struct LicenseText {
+    pub path: Utf8PathBuf,
+    pub text: String,
+}
+
+fn is_license_file_name(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    ["LICENSE", "LICENCE", "COPYING", "UNLICENSE"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
 }
 
 impl DependencyDetails {
     #[must_use]
     pub fn new(package: &Package) -> Self {
+        Self::with_author_separator(package, DEFAULT_AUTHOR_SEPARATOR)
+    }
+
+    /// Like [`DependencyDetails::new`], but joins the authors with
+    /// `separator` instead of `|`.
+    #[must_use]
+    pub fn with_author_separator(package: &Package, separator: &str) -> Self {
         let authors = if package.authors.is_empty() {
             None
         } else {
-            Some(package.authors.to_owned().join("|"))
+            Some(package.authors.join(separator))
         };
         Self {
             name: package.name.to_owned(),
@@ -93,118 +731,2246 @@ impl DependencyDetails {
                 .description
                 .to_owned()
                 .map(|s| s.trim().replace('\n', " ")),
+            yanked: None,
+            dependents: None,
+            is_direct: None,
+            kinds: None,
+            dependency_path: None,
+            override_reason: None,
+            override_approved_by: None,
+            tags: None,
+            license_file_sha256: None,
+            features: None,
+            edition: None,
+            rust_version: None,
+            manifest_path: package.manifest_path.clone(),
+            license_file_path: package.license_file(),
+            crates_io: package.source.as_ref().is_some_and(|s| s.is_crates_io()),
+        }
+    }
+
+    /// Reads the license texts shipped with the crate: its `license-file` and
+    /// every `LICENSE*`, `LICENCE*`, `COPYING*` or `UNLICENSE` file next to
+    /// its `Cargo.toml`.
+    pub fn license_texts(&self) -> io::Result<Vec<LicenseText>> {
+        let Some(package_dir) = self.manifest_path.parent() else {
+            return Ok(Vec::new());
+        };
+        let mut paths = Vec::new();
+        if let Some(license_file) = &self.license_file_path {
+            paths.push(license_file.clone());
+        }
+        for entry in package_dir.read_dir_utf8()? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() && is_license_file_name(entry.file_name()) {
+                paths.push(entry.into_path());
+            }
         }
+        paths.sort();
+        paths.dedup();
+        paths
+            .into_iter()
+            .map(|path| {
+                let text = std::fs::read_to_string(&path)?;
+                Ok(LicenseText { path, text })
+            })
+            .collect()
     }
 }
 
-#[derive(Debug, Serialize, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
-struct GitlabDependency {
-    name: String,
-    version: Version,
-    package_manager: &'static str,
-    path: String,
-    licenses: Vec<&'static str>,
+/// A crate whose license text is part of a [`LicenseTextGroup`].
+#[derive(Debug, Clone)]
+pub struct BundledCrate {
+    pub name: String,
+    pub version: Version,
+    /// Copyright lines stripped from the text when normalizing.
+    pub copyright: Vec<String>,
 }
 
-#[derive(Debug, Serialize, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
-struct GitlabLicense {
-    id: &'static str,
-    name: &'static str,
-    url: String,
+/// A unique license text and the crates shipping it.
+#[derive(Debug, Clone)]
+pub struct LicenseTextGroup {
+    pub text: String,
+    pub crates: Vec<BundledCrate>,
 }
 
-impl GitlabLicense {
-    fn parse_licenses(dependency: &DependencyDetails) -> Result<HashSet<Self>> {
-        let Some(license) = &dependency.license else {
-            return Ok(HashSet::new());
-        };
-        let expression = spdx::Expression::parse_mode(license, spdx::ParseMode::LAX)?;
-        Ok(expression
-            .requirements()
-            .flat_map(|req| {
-                req.req.license.id().map(|license| Self {
-                    id: license.name,
-                    name: license.full_name,
-                    url: Default::default(),
-                })
-            })
-            .collect())
-    }
+/// License texts of a set of crates, deduplicated by content.
+#[derive(Debug, Clone, Default)]
+pub struct LicenseBundle {
+    pub groups: Vec<LicenseTextGroup>,
+    /// Crates without any license text file.
+    pub missing: Vec<(String, Version)>,
 }
 
-#[derive(Debug, Serialize, Clone)]
-struct GitlabLicenseScanningReport {
-    version: &'static str,
-    licenses: HashSet<GitlabLicense>,
-    dependencies: Vec<GitlabDependency>,
+/// Splits `text` into its copyright lines and the remaining text with
+/// whitespace collapsed, so that texts only differing in copyright holders,
+/// years or formatting compare equal.
+fn normalize_license_text(text: &str) -> (String, Vec<String>) {
+    let (copyright, rest): (Vec<&str>, Vec<&str>) = text
+        .lines()
+        .map(str::trim)
+        .partition(|line| line.to_ascii_lowercase().starts_with("copyright"));
+    let text = rest
+        .split(|line| line.is_empty())
+        .map(|paragraph| {
+            paragraph
+                .iter()
+                .flat_map(|l| l.split_whitespace())
+                .join(" ")
+        })
+        .filter(|paragraph| !paragraph.is_empty())
+        .join("\n\n");
+    (text, copyright.into_iter().map(str::to_owned).collect())
 }
 
-impl TryFrom<&[DependencyDetails]> for GitlabLicenseScanningReport {
-    type Error = anyhow::Error;
-    fn try_from(dependencies: &[DependencyDetails]) -> Result<Self> {
-        let mut licenses = HashSet::new();
-        let dependencies = dependencies
-            .iter()
-            .cloned()
-            .map(|dependency| {
-                let dep_licenses = GitlabLicense::parse_licenses(&dependency)?;
-                let license_ids = dep_licenses.iter().map(|license| license.id).collect();
-                licenses.extend(dep_licenses);
-                Ok::<_, Self::Error>(GitlabDependency {
-                    name: dependency.name,
-                    version: dependency.version,
-                    package_manager: "cargo",
-                    path: Default::default(),
-                    licenses: license_ids,
+/// Groups the license texts of `dependencies` by content. With
+/// `normalize_text`, texts that only differ in whitespace or copyright lines
+/// are merged and each crate's copyright lines are kept alongside it.
+pub fn bundle_license_texts(
+    dependencies: &[DependencyDetails],
+    normalize_text: bool,
+) -> Result<LicenseBundle> {
+    let mut bundle = LicenseBundle::default();
+    let mut groups: HashMap<String, LicenseTextGroup> = HashMap::new();
+    for dependency in dependencies {
+        let texts = dependency.license_texts()?;
+        if texts.is_empty() {
+            bundle
+                .missing
+                .push((dependency.name.clone(), dependency.version.clone()));
+        }
+        for LicenseText { text, .. } in texts {
+            let (key, copyright) = if normalize_text {
+                normalize_license_text(&text)
+            } else {
+                (text.trim().to_owned(), Vec::new())
+            };
+            groups
+                .entry(key.clone())
+                .or_insert_with(|| LicenseTextGroup {
+                    text: key,
+                    crates: Vec::new(),
                 })
-            })
-            .try_collect()?;
-
-        Ok(GitlabLicenseScanningReport {
-            version: "2.1",
-            dependencies,
-            licenses,
-        })
+                .crates
+                .push(BundledCrate {
+                    name: dependency.name.clone(),
+                    version: dependency.version.clone(),
+                    copyright,
+                });
+        }
     }
+    bundle.groups = groups.into_values().collect();
+    bundle.groups.sort_by(|a, b| {
+        b.crates
+            .len()
+            .cmp(&a.crates.len())
+            .then(a.text.cmp(&b.text))
+    });
+    Ok(bundle)
 }
 
-#[derive(Default)]
-pub struct GetDependenciesOpt {
-    pub avoid_dev_deps: bool,
-    pub avoid_build_deps: bool,
-    pub avoid_proc_macros: bool,
-    pub direct_deps_only: bool,
-    pub root_only: bool,
+/// Coarse risk category of a license, ordered from least to most restrictive.
+#[derive(Debug, Serialize, Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq)]
+pub enum LicenseCategory {
+    Permissive,
+    WeakCopyleft,
+    StrongCopyleft,
+    Unknown,
 }
 
-pub fn get_dependencies_from_cargo_lock(
-    metadata_command: MetadataCommand,
-    opt: GetDependenciesOpt,
-) -> Result<Vec<DependencyDetails>> {
-    let metadata = metadata_command.exec()?;
-
-    let node_name_filter = get_node_name_filter(&metadata, &opt);
-    let proc_macro_exclusions = get_proc_macro_node_names(&metadata, &opt);
-
-    let connected = {
-        let resolve = metadata.resolve.as_ref().expect("missing `resolve`");
+/// License id prefixes of copyleft licenses whose obligations are limited to
+/// the licensed files or library itself.
+const WEAK_COPYLEFT_PREFIXES: &[&str] = &["LGPL-", "MPL-", "EPL-", "CDDL-", "CPL-", "MS-RL"];
 
-        let deps = resolve
-            .nodes
+impl LicenseCategory {
+    fn of_license(license: &spdx::LicenseReq) -> Self {
+        let Some(id) = license.license.id() else {
+            return LicenseCategory::Unknown;
+        };
+        if WEAK_COPYLEFT_PREFIXES
             .iter()
-            .map(|Node { id, deps, .. }| (id, deps))
-            .collect::<HashMap<_, _>>();
+            .any(|prefix| id.name.starts_with(prefix))
+        {
+            LicenseCategory::WeakCopyleft
+        } else if id.is_copyleft() {
+            LicenseCategory::StrongCopyleft
+        } else if id.is_osi_approved() || id.is_fsf_free_libre() {
+            LicenseCategory::Permissive
+        } else {
+            LicenseCategory::Unknown
+        }
+    }
 
-        let missing_dep_kinds = deps
-            .values()
-            .flat_map(|d| d.iter())
-            .any(|NodeDep { dep_kinds, .. }| dep_kinds.is_empty());
+    /// Categorizes a license expression. `AND` takes the most restrictive
+    /// category of its operands and `OR` the least restrictive one, since the
+    /// licensee may choose. Unparseable expressions are `Unknown`.
+    #[must_use]
+    pub fn of_expression(license: &str) -> Self {
+        Self::of_expression_with(license, Interpretation::BestCase)
+    }
 
-        if missing_dep_kinds && opt.avoid_dev_deps {
-            eprintln!("warning: Cargo 1.41+ is required for `--avoid-dev-deps`");
-        }
-        if missing_dep_kinds && opt.avoid_build_deps {
-            eprintln!("warning: Cargo 1.41+ is required for `--avoid-build-deps`");
+    /// Like [`LicenseCategory::of_expression`], but `OR` takes the most
+    /// restrictive category of its operands in the worst case.
+    #[must_use]
+    pub fn of_expression_with(license: &str, interpretation: Interpretation) -> Self {
+        let Ok(expression) = spdx::Expression::parse_mode(license, spdx::ParseMode::LAX) else {
+            return LicenseCategory::Unknown;
+        };
+        let mut stack = Vec::new();
+        for node in expression.iter() {
+            let category = match node {
+                spdx::expression::ExprNode::Req(req) => Self::of_license(&req.req),
+                spdx::expression::ExprNode::Op(op) => {
+                    let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+                        return LicenseCategory::Unknown;
+                    };
+                    match op {
+                        spdx::expression::Operator::And => std::cmp::max(lhs, rhs),
+                        spdx::expression::Operator::Or => match interpretation {
+                            Interpretation::BestCase => std::cmp::min(lhs, rhs),
+                            Interpretation::WorstCase => std::cmp::max(lhs, rhs),
+                        },
+                    }
+                }
+            };
+            stack.push(category);
+        }
+        stack.pop().unwrap_or(LicenseCategory::Unknown)
+    }
+}
+
+/// Counts the crates in every [`LicenseCategory`], including empty ones.
+/// Crates without a license expression are `Unknown`.
+#[must_use]
+pub fn count_by_category(
+    dependencies: &[DependencyDetails],
+    interpretation: Interpretation,
+) -> BTreeMap<LicenseCategory, usize> {
+    let mut counts = [
+        LicenseCategory::Permissive,
+        LicenseCategory::WeakCopyleft,
+        LicenseCategory::StrongCopyleft,
+        LicenseCategory::Unknown,
+    ]
+    .into_iter()
+    .map(|category| (category, 0))
+    .collect::<BTreeMap<_, _>>();
+    for dependency in dependencies {
+        let category = dependency
+            .license
+            .as_deref()
+            .map_or(LicenseCategory::Unknown, |license| {
+                LicenseCategory::of_expression_with(license, interpretation)
+            });
+        *counts.entry(category).or_default() += 1;
+    }
+    counts
+}
+
+/// How the choices offered by `OR` expressions are interpreted when
+/// categorizing licenses and checking them against a policy.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Interpretation {
+    /// The licensee picks the most permissive option.
+    #[default]
+    BestCase,
+    /// Every option must be acceptable, as if `OR` were `AND`.
+    WorstCase,
+}
+
+impl fmt::Display for LicenseCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LicenseCategory::Permissive => "Permissive",
+            LicenseCategory::WeakCopyleft => "WeakCopyleft",
+            LicenseCategory::StrongCopyleft => "StrongCopyleft",
+            LicenseCategory::Unknown => "Unknown",
+        })
+    }
+}
+
+/// SPDX metadata about a single license identifier.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct LicenseInfo {
+    pub id: &'static str,
+    pub full_name: &'static str,
+    pub osi_approved: bool,
+    pub fsf_free_libre: bool,
+    pub copyleft: bool,
+    pub deprecated: bool,
+    /// Whether `id` was given with a `+`, for this version or any later one,
+    /// and has no `-or-later` identifier of its own.
+    pub or_later: bool,
+    pub url: String,
+}
+
+impl LicenseInfo {
+    /// Looks up `id` in the SPDX license list, falling back to an imprecise
+    /// match for common misspellings such as `Apache 2.0`. A trailing `+` is
+    /// looked up as the `-or-later` identifier, if there is one.
+    ///
+    /// The imprecise match has to cover all of `id`, see [`LicenseInfo::suggest`]
+    /// for the license a longer `id` starts with.
+    #[must_use]
+    pub fn lookup(id: &str) -> Option<Self> {
+        let (base, or_later) = match id.strip_suffix('+') {
+            Some(base) => (base, true),
+            None => (id, false),
+        };
+        if base.contains('+') {
+            return None;
+        }
+        if or_later {
+            let later = format!("{}-or-later", base.strip_suffix("-only").unwrap_or(base));
+            if let Some(license) = spdx::license_id(&later) {
+                return Some(Self::of(license, false));
+            }
+        }
+        let license = spdx::license_id(base).or_else(|| {
+            spdx::imprecise_license_id(base)
+                .filter(|&(_, len)| len == base.len())
+                .map(|(license, _)| license)
+        })?;
+        Some(Self::of(license, or_later))
+    }
+
+    /// The license an unknown `id` most likely refers to, e.g. `MIT` for
+    /// `MIT-foo`.
+    #[must_use]
+    pub fn suggest(id: &str) -> Option<&'static str> {
+        spdx::imprecise_license_id(id.trim_end_matches('+')).map(|(license, _)| license.name)
+    }
+
+    fn of(license: spdx::LicenseId, or_later: bool) -> Self {
+        Self {
+            id: license.name,
+            full_name: license.full_name,
+            osi_approved: license.is_osi_approved(),
+            fsf_free_libre: license.is_fsf_free_libre(),
+            copyleft: license.is_copyleft(),
+            deprecated: license.is_deprecated(),
+            or_later,
+            url: format!("https://spdx.org/licenses/{}.html", license.name),
+        }
+    }
+}
+
+/// A high-level obligation imposed by a license on redistribution.
+#[derive(Debug, Serialize, Clone, Copy, Hash, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Obligation {
+    /// The copyright notice and license text must be retained.
+    Attribution,
+    /// Modified files must carry a notice that they were changed.
+    StateChanges,
+    /// The source code must be made available when distributing.
+    DiscloseSource,
+    /// Derived works must be released under the same license.
+    SameLicense,
+    /// Users interacting over a network must be offered the source.
+    NetworkDisclosure,
+    /// The license grants a patent license from contributors.
+    PatentGrant,
+    /// The license is not in the obligations table.
+    ReviewRequired,
+}
+
+impl fmt::Display for Obligation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Obligation::Attribution => "attribution",
+            Obligation::StateChanges => "state changes",
+            Obligation::DiscloseSource => "source disclosure",
+            Obligation::SameLicense => "same license",
+            Obligation::NetworkDisclosure => "network source disclosure",
+            Obligation::PatentGrant => "patent grant",
+            Obligation::ReviewRequired => "review required",
+        })
+    }
+}
+
+/// Obligations of common licenses, keyed on the SPDX identifier without its
+/// `-only`/`-or-later` suffix.
+const OBLIGATIONS: &[(&str, &[Obligation])] = {
+    use Obligation::{
+        Attribution, DiscloseSource, NetworkDisclosure, PatentGrant, SameLicense, StateChanges,
+    };
+    &[
+        ("0BSD", &[]),
+        (
+            "AGPL-3.0",
+            &[
+                Attribution,
+                StateChanges,
+                DiscloseSource,
+                SameLicense,
+                NetworkDisclosure,
+                PatentGrant,
+            ],
+        ),
+        ("Apache-2.0", &[Attribution, StateChanges, PatentGrant]),
+        ("BSD-2-Clause", &[Attribution]),
+        ("BSD-3-Clause", &[Attribution]),
+        ("BSL-1.0", &[Attribution]),
+        ("CC0-1.0", &[]),
+        ("CDLA-Permissive-2.0", &[Attribution]),
+        ("EPL-2.0", &[Attribution, DiscloseSource, PatentGrant]),
+        (
+            "GPL-2.0",
+            &[Attribution, StateChanges, DiscloseSource, SameLicense],
+        ),
+        (
+            "GPL-3.0",
+            &[
+                Attribution,
+                StateChanges,
+                DiscloseSource,
+                SameLicense,
+                PatentGrant,
+            ],
+        ),
+        ("ISC", &[Attribution]),
+        (
+            "LGPL-2.1",
+            &[Attribution, StateChanges, DiscloseSource, SameLicense],
+        ),
+        (
+            "LGPL-3.0",
+            &[
+                Attribution,
+                StateChanges,
+                DiscloseSource,
+                SameLicense,
+                PatentGrant,
+            ],
+        ),
+        ("MIT", &[Attribution]),
+        ("MIT-0", &[]),
+        ("MPL-2.0", &[Attribution, DiscloseSource, PatentGrant]),
+        ("OpenSSL", &[Attribution]),
+        ("Unicode-3.0", &[Attribution]),
+        ("Unicode-DFS-2016", &[Attribution]),
+        ("Unlicense", &[]),
+        ("Zlib", &[Attribution, StateChanges]),
+    ]
+};
+
+impl Obligation {
+    /// Looks up the obligations of a single SPDX license identifier. Licenses
+    /// missing from the table require review.
+    #[must_use]
+    pub fn of_license(id: &str) -> Vec<Self> {
+        let base = id
+            .strip_suffix("-only")
+            .or_else(|| id.strip_suffix("-or-later"))
+            .unwrap_or(id);
+        OBLIGATIONS
+            .iter()
+            .find(|(license, _)| *license == base)
+            .map_or_else(
+                || vec![Obligation::ReviewRequired],
+                |(_, obligations)| obligations.to_vec(),
+            )
+    }
+}
+
+const GPL_2_ONLY: &[&str] = &["GPL-2.0", "GPL-2.0-only"];
+const GPL_3_FAMILY: &[&str] = &[
+    "GPL-3.0",
+    "GPL-3.0+",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "LGPL-3.0",
+    "LGPL-3.0+",
+    "LGPL-3.0-only",
+    "LGPL-3.0-or-later",
+    "AGPL-3.0",
+    "AGPL-3.0+",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+];
+const GPL_ANY: &[&str] = &[
+    "GPL-2.0",
+    "GPL-2.0+",
+    "GPL-2.0-only",
+    "GPL-2.0-or-later",
+    "GPL-3.0",
+    "GPL-3.0+",
+    "GPL-3.0-only",
+    "GPL-3.0-or-later",
+    "AGPL-3.0",
+    "AGPL-3.0+",
+    "AGPL-3.0-only",
+    "AGPL-3.0-or-later",
+];
+
+/// Pairs of license sets known to be incompatible with each other, following
+/// the FSF's list of GPL-incompatible licenses. This is deliberately
+/// conservative: only well-established conflicts with the GPL are listed,
+/// and pairs not in the table are not assumed to be compatible.
+const INCOMPATIBLE_LICENSES: &[(&[&str], &[&str])] = &[
+    (GPL_2_ONLY, &["Apache-2.0"]),
+    (GPL_2_ONLY, GPL_3_FAMILY),
+    (
+        GPL_ANY,
+        &[
+            "Apache-1.1",
+            "BSD-4-Clause",
+            "CDDL-1.0",
+            "EPL-1.0",
+            "OpenSSL",
+        ],
+    ),
+];
+
+fn are_incompatible(a: &str, b: &str) -> bool {
+    INCOMPATIBLE_LICENSES.iter().any(|(lhs, rhs)| {
+        lhs.contains(&a) && rhs.contains(&b) || lhs.contains(&b) && rhs.contains(&a)
+    })
+}
+
+/// Returns the pairs of licenses known to be incompatible that `license`
+/// requires to be combined with `AND`. An expression is only flagged when
+/// every choice offered by its `OR`s contains such a pair, since otherwise
+/// the licensee may pick a compatible one.
+#[must_use]
+pub fn incompatible_licenses(license: &str) -> Vec<(String, String)> {
+    let Ok(expression) = spdx::Expression::parse_mode(license, spdx::ParseMode::LAX) else {
+        return Vec::new();
+    };
+    // The expression in disjunctive normal form: every choice is a list of
+    // licenses that all apply.
+    let mut stack: Vec<Vec<Vec<String>>> = Vec::new();
+    for node in expression.iter() {
+        let choices = match node {
+            spdx::expression::ExprNode::Req(req) => vec![vec![req.req.license.to_string()]],
+            spdx::expression::ExprNode::Op(op) => {
+                let (Some(rhs), Some(lhs)) = (stack.pop(), stack.pop()) else {
+                    return Vec::new();
+                };
+                match op {
+                    spdx::expression::Operator::Or => lhs.into_iter().chain(rhs).collect(),
+                    spdx::expression::Operator::And => lhs
+                        .iter()
+                        .cartesian_product(&rhs)
+                        .map(|(lhs, rhs)| lhs.iter().chain(rhs).cloned().collect())
+                        .collect(),
+                }
+            }
+        };
+        stack.push(choices);
+    }
+    let Some(choices) = stack.pop() else {
+        return Vec::new();
+    };
+    let conflicts = choices
+        .iter()
+        .map(|choice| {
+            choice
+                .iter()
+                .tuple_combinations()
+                .filter(|(a, b)| are_incompatible(a, b))
+                .map(|(a, b)| (a.clone(), b.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    if conflicts.iter().any(Vec::is_empty) {
+        return Vec::new();
+    }
+    conflicts.into_iter().flatten().unique().collect()
+}
+
+/// Warns about every crate whose license combines licenses known to be
+/// incompatible, see [`incompatible_licenses`].
+#[must_use]
+pub fn check_compatibility(dependencies: &[DependencyDetails]) -> Vec<Warning> {
+    dependencies
+        .iter()
+        .filter_map(|dependency| {
+            let pairs = incompatible_licenses(dependency.license.as_deref()?);
+            if pairs.is_empty() {
+                return None;
+            }
+            Some(Warning::new(
+                WarningKind::IncompatibleLicenses,
+                format!(
+                    "license of `{} {}` combines incompatible licenses: {}",
+                    dependency.name,
+                    dependency.version,
+                    pairs
+                        .iter()
+                        .map(|(a, b)| format!("`{a}` and `{b}`"))
+                        .join(", ")
+                ),
+            ))
+        })
+        .collect()
+}
+
+/// Returns `true` if code under `dependency` may be distributed as part of a
+/// work licensed under `project`, see [`verdict`].
+fn can_include(project: &spdx::LicenseReq, dependency: &spdx::LicenseReq) -> bool {
+    // The same license, or a later version the dependency allows. A project
+    // under a version "or later" is held to that version.
+    let license = match &project.license {
+        spdx::LicenseItem::Spdx { id, .. } => spdx::LicenseItem::Spdx {
+            id: *id,
+            or_later: false,
+        },
+        other => other.clone(),
+    };
+    if spdx::Licensee::new(license, project.exception).satisfies(dependency) {
+        return true;
+    }
+    let (Some(project_id), Some(dependency_id)) = (project.license.id(), dependency.license.id())
+    else {
+        return false;
+    };
+    let (project_name, dependency_name) = (project_id.name, dependency_id.name);
+    if are_incompatible(project_name, dependency_name) {
+        return false;
+    }
+    match LicenseCategory::of_license(dependency) {
+        LicenseCategory::Permissive => true,
+        // The copyleft of the MPL-2.0 stays within its files, and the LGPLs
+        // may be relicensed under the GPL. Other weak copyleft licenses
+        // restrict statically linked works, like every Rust binary.
+        LicenseCategory::WeakCopyleft => {
+            dependency_name.starts_with("MPL-2.0")
+                || dependency_name.starts_with("LGPL-") && GPL_ANY.contains(&project_name)
+        }
+        // The AGPL-3.0 explicitly allows combining with GPL-3.0 code.
+        LicenseCategory::StrongCopyleft => {
+            project_name.starts_with("AGPL-3.0") && dependency_name.starts_with("GPL-3.0")
+        }
+        LicenseCategory::Unknown => false,
+    }
+}
+
+/// Whether every crate may be distributed under the project's license.
+#[derive(Debug, Serialize, Clone)]
+pub struct Verdict<'a> {
+    pub project_license: String,
+    pub passed: bool,
+    /// Crates whose license can't be shown to allow it.
+    pub incompatible: Vec<&'a DependencyDetails>,
+}
+
+/// Checks whether every crate may be distributed as part of a work licensed
+/// under `project_license`. A crate passes when one of the choices its `OR`s
+/// offer only contains licenses that may be included under every license of
+/// `project_license`.
+///
+/// The compatibility matrix is deliberately conservative: permissive
+/// licenses are accepted unless listed as incompatible with the GPL, the
+/// MPL-2.0 is the only weak copyleft license accepted in a permissively
+/// licensed project, and copyleft licenses are only accepted under a project
+/// license they allow relicensing to. Crates without an SPDX license
+/// expression, or with licenses the matrix doesn't know, always fail. A
+/// passing verdict is no substitute for legal advice.
+pub fn verdict<'a>(
+    dependencies: &'a [DependencyDetails],
+    project_license: &str,
+) -> Result<Verdict<'a>> {
+    let project =
+        spdx::Expression::parse_mode(project_license, spdx::ParseMode::LAX).map_err(|source| {
+            CargoLicenseError::ProjectLicense {
+                license: project_license.to_owned(),
+                source,
+            }
+        })?;
+    let incompatible = dependencies
+        .iter()
+        .filter(|dependency| {
+            !dependency.license.as_deref().is_some_and(|license| {
+                spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).is_ok_and(
+                    |expression| {
+                        expression.evaluate(|req| {
+                            project
+                                .requirements()
+                                .all(|project| can_include(&project.req, req))
+                        })
+                    },
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    Ok(Verdict {
+        project_license: project_license.to_owned(),
+        passed: incompatible.is_empty(),
+        incompatible,
+    })
+}
+
+/// A distinct license of the report along with its obligations.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct LicenseObligations {
+    pub license: String,
+    pub obligations: Vec<Obligation>,
+    /// Names of the crates offering this license.
+    pub crates: Vec<String>,
+}
+
+/// Lists the obligations of every distinct license in `dependencies`.
+/// Expressions are split into their licenses; crates without a parseable
+/// license are listed under their raw license string, or `N/A`, and require
+/// review.
+#[must_use]
+pub fn license_obligations(dependencies: &[DependencyDetails]) -> Vec<LicenseObligations> {
+    let mut crates = BTreeMap::<String, BTreeSet<String>>::new();
+    for dependency in dependencies {
+        let license = dependency.license.as_deref().unwrap_or("N/A");
+        let licenses = match spdx::Expression::parse_mode(license, spdx::ParseMode::LAX) {
+            Ok(expression) => expression
+                .requirements()
+                .map(|req| req.req.license.to_string())
+                .collect(),
+            Err(_) => vec![license.to_owned()],
+        };
+        for license in licenses {
+            crates
+                .entry(license)
+                .or_default()
+                .insert(dependency.name.clone());
+        }
+    }
+    crates
+        .into_iter()
+        .map(|(license, crates)| LicenseObligations {
+            obligations: Obligation::of_license(&license),
+            license,
+            crates: crates.into_iter().collect(),
+        })
+        .collect()
+}
+
+/// How many crates of a report have usable license metadata.
+#[derive(Debug, Serialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SpdxStats {
+    /// Crates whose license is a parseable SPDX expression.
+    pub valid: usize,
+    /// Crates without a license but with a `license-file`.
+    pub license_file_only: usize,
+    /// Crates whose license is not a parseable SPDX expression.
+    pub invalid: usize,
+    /// Crates with neither a license nor a `license-file`.
+    pub missing: usize,
+}
+
+impl SpdxStats {
+    #[must_use]
+    pub fn of(dependencies: &[DependencyDetails]) -> Self {
+        let mut stats = Self::default();
+        for dependency in dependencies {
+            let count = match (&dependency.license, &dependency.license_file) {
+                (Some(license), _) => {
+                    if spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).is_ok() {
+                        &mut stats.valid
+                    } else {
+                        &mut stats.invalid
+                    }
+                }
+                (None, Some(_)) => &mut stats.license_file_only,
+                (None, None) => &mut stats.missing,
+            };
+            *count += 1;
+        }
+        stats
+    }
+}
+
+/// A small set of licenses satisfying the license expression of every crate,
+/// e.g. to decide which licenses to elect for dual-licensed crates.
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+pub struct MinimalLicenseSet {
+    /// The licenses to choose, sorted.
+    pub licenses: Vec<String>,
+    /// Crates whose license is satisfied by the licenses.
+    pub crates: usize,
+    /// Crates without a parseable SPDX expression, which can't be satisfied.
+    pub skipped: usize,
+}
+
+impl MinimalLicenseSet {
+    /// Picks licenses greedily, every round choosing the license that
+    /// satisfies the most remaining crates, then the one the most of them
+    /// mention, then the first by name. Finding the smallest set is a set
+    /// cover problem, so the result is not always optimal.
+    #[must_use]
+    pub fn of(dependencies: &[DependencyDetails]) -> Self {
+        let mut expressions = dependencies
+            .iter()
+            .filter_map(|dependency| {
+                spdx::Expression::parse_mode(dependency.license.as_deref()?, spdx::ParseMode::LAX)
+                    .ok()
+            })
+            .collect::<Vec<_>>();
+        let crates = expressions.len();
+        let satisfied = |expression: &spdx::Expression, chosen: &BTreeSet<String>| {
+            expression.evaluate(|req| chosen.contains(&req.to_string()))
+        };
+
+        let mut chosen = BTreeSet::new();
+        while !expressions.is_empty() {
+            let candidates = expressions
+                .iter()
+                .flat_map(|expression| expression.requirements())
+                .map(|requirement| requirement.req.to_string())
+                .collect::<BTreeSet<_>>();
+            let best = candidates
+                .into_iter()
+                .max_by_key(|candidate| {
+                    let mut with = chosen.clone();
+                    with.insert(candidate.clone());
+                    let satisfies = expressions
+                        .iter()
+                        .filter(|expression| satisfied(expression, &with))
+                        .count();
+                    let mentions = expressions
+                        .iter()
+                        .filter(|expression| {
+                            expression
+                                .requirements()
+                                .any(|requirement| requirement.req.to_string() == *candidate)
+                        })
+                        .count();
+                    (satisfies, mentions, std::cmp::Reverse(candidate.clone()))
+                })
+                .expect("an unsatisfied expression has requirements");
+            chosen.insert(best);
+            expressions.retain(|expression| !satisfied(expression, &chosen));
+        }
+
+        Self {
+            licenses: chosen.into_iter().collect(),
+            crates,
+            skipped: dependencies.len() - crates,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+struct GitlabDependency {
+    name: String,
+    version: Version,
+    package_manager: &'static str,
+    path: String,
+    licenses: Vec<&'static str>,
+}
+
+#[derive(Debug, Serialize, Clone, Hash, Ord, PartialOrd, Eq, PartialEq)]
+struct GitlabLicense {
+    id: &'static str,
+    name: &'static str,
+    url: String,
+    /// The SPDX exception of a `WITH` expression, such as `LLVM-exception`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    exception: Option<&'static str>,
+}
+
+impl GitlabLicense {
+    fn parse_licenses(dependency: &DependencyDetails) -> Result<HashSet<Self>> {
+        let Some(license) = &dependency.license else {
+            return Ok(HashSet::new());
+        };
+        let expression =
+            spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).map_err(|source| {
+                CargoLicenseError::Spdx {
+                    expression: license.clone(),
+                    source,
+                }
+            })?;
+        Ok(expression
+            .requirements()
+            .flat_map(|req| {
+                req.req.license.id().map(|license| Self {
+                    id: license.name,
+                    name: license.full_name,
+                    url: Default::default(),
+                    exception: req.req.exception.map(|exception| exception.name),
+                })
+            })
+            .collect())
+    }
+}
+
+/// A package of an ORT analyzer result.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+struct OrtPackage {
+    id: String,
+    declared_licenses: Vec<String>,
+    declared_licenses_processed: OrtProcessedLicenses,
+    description: String,
+    homepage_url: String,
+    binary_artifact: OrtRemoteArtifact,
+    source_artifact: OrtRemoteArtifact,
+    vcs: OrtVcsInfo,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+struct OrtProcessedLicenses {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    spdx_expression: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+struct OrtRemoteArtifact {
+    url: String,
+    hash: OrtHash,
+}
+
+#[derive(Debug, Serialize, Clone, Default, PartialEq, Eq)]
+struct OrtHash {
+    value: String,
+    algorithm: String,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+struct OrtVcsInfo {
+    #[serde(rename = "type")]
+    kind: String,
+    url: String,
+    revision: String,
+    path: String,
+}
+
+impl From<&DependencyDetails> for OrtPackage {
+    fn from(dependency: &DependencyDetails) -> Self {
+        let source_url = if dependency.crates_io {
+            format!(
+                "https://crates.io/api/v1/crates/{}/{}/download",
+                dependency.name, dependency.version
+            )
+        } else {
+            String::new()
+        };
+        Self {
+            id: format!("Crate::{}:{}", dependency.name, dependency.version),
+            declared_licenses: dependency.license.iter().cloned().collect(),
+            declared_licenses_processed: OrtProcessedLicenses {
+                spdx_expression: dependency.license.clone(),
+            },
+            description: dependency.description.clone().unwrap_or_default(),
+            homepage_url: String::new(),
+            binary_artifact: OrtRemoteArtifact::default(),
+            source_artifact: OrtRemoteArtifact {
+                url: source_url,
+                hash: OrtHash::default(),
+            },
+            vcs: OrtVcsInfo {
+                kind: if dependency.repository.is_some() {
+                    "Git".to_owned()
+                } else {
+                    String::new()
+                },
+                url: dependency.repository.clone().unwrap_or_default(),
+                revision: String::new(),
+                path: String::new(),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+struct GitlabLicenseScanningReport {
+    version: &'static str,
+    licenses: HashSet<GitlabLicense>,
+    dependencies: Vec<GitlabDependency>,
+}
+
+impl TryFrom<&[DependencyDetails]> for GitlabLicenseScanningReport {
+    type Error = CargoLicenseError;
+    fn try_from(dependencies: &[DependencyDetails]) -> Result<Self> {
+        let mut licenses = HashSet::new();
+        let dependencies = dependencies
+            .iter()
+            .cloned()
+            .map(|dependency| {
+                let dep_licenses = GitlabLicense::parse_licenses(&dependency)?;
+                let license_ids = dep_licenses.iter().map(|license| license.id).collect();
+                licenses.extend(dep_licenses);
+                Ok::<_, Self::Error>(GitlabDependency {
+                    name: dependency.name,
+                    version: dependency.version,
+                    package_manager: "cargo",
+                    path: Default::default(),
+                    licenses: license_ids,
+                })
+            })
+            .try_collect()?;
+
+        Ok(GitlabLicenseScanningReport {
+            version: "2.1",
+            dependencies,
+            licenses,
+        })
+    }
+}
+
+fn cargo_home() -> Option<PathBuf> {
+    std::env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map(|home| Path::new(&home).join(".cargo"))
+        })
+}
+
+/// Returns the `build.target` triple configured for builds started in `dir`,
+/// from `CARGO_BUILD_TARGET` or the closest `.cargo/config.toml` (or
+/// `.cargo/config`) in `dir`, its ancestors or cargo's home directory.
+///
+/// Multiple targets and custom target specification files are ignored,
+/// since they can't be passed to `cargo metadata --filter-platform`.
+pub fn configured_build_target(dir: &Path) -> Result<Option<String>> {
+    fn single_triple(target: &toml::Value) -> Option<String> {
+        let triple = match target {
+            toml::Value::String(triple) => triple,
+            toml::Value::Array(targets) => match targets.as_slice() {
+                [toml::Value::String(triple)] => triple,
+                _ => return None,
+            },
+            _ => return None,
+        };
+        (!triple.ends_with(".json")).then(|| triple.clone())
+    }
+
+    if let Some(target) = std::env::var_os("CARGO_BUILD_TARGET") {
+        return Ok(single_triple(&toml::Value::String(
+            target.to_string_lossy().into_owned(),
+        )));
+    }
+    let config_dirs = dir
+        .ancestors()
+        .map(|dir| dir.join(".cargo"))
+        .chain(cargo_home());
+    for config_dir in config_dirs {
+        for file_name in ["config.toml", "config"] {
+            let path = config_dir.join(file_name);
+            let Ok(contents) = std::fs::read_to_string(&path) else {
+                continue;
+            };
+            let config: toml::Table = toml::from_str(&contents)?;
+            if let Some(target) = config.get("build").and_then(|build| build.get("target")) {
+                return Ok(single_triple(target));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// A version entry of the crates.io registry index.
+#[derive(Debug, Deserialize)]
+struct IndexEntry {
+    vers: Version,
+    #[serde(default)]
+    yanked: bool,
+    /// When the version was published, only recorded by recent index entries.
+    pubtime: Option<String>,
+}
+
+/// Parses a file of cargo's registry index cache, which stores the index
+/// entries as NUL separated JSON lines after a small header.
+fn parse_index_cache(bytes: &[u8]) -> Vec<IndexEntry> {
+    bytes
+        .split(|&b| b == 0)
+        .filter(|chunk| chunk.first() == Some(&b'{'))
+        .filter_map(|chunk| serde_json::from_slice(chunk).ok())
+        .collect()
+}
+
+/// The crates.io registry index as cached locally by cargo. Nothing is
+/// fetched from the network, so the data is as fresh as cargo's last update.
+struct RegistryIndex {
+    cache_dirs: Vec<PathBuf>,
+}
+
+impl RegistryIndex {
+    fn open() -> Option<Self> {
+        let cargo_home = cargo_home()?;
+        let cache_dirs = cargo_home
+            .join("registry")
+            .join("index")
+            .read_dir()
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| {
+                        name.starts_with("index.crates.io-") || name.starts_with("github.com-")
+                    })
+            })
+            .map(|path| path.join(".cache"))
+            .filter(|path| path.is_dir())
+            .collect::<Vec<_>>();
+        (!cache_dirs.is_empty()).then_some(Self { cache_dirs })
+    }
+
+    fn entries(&self, name: &str) -> Vec<IndexEntry> {
+        let name = name.to_ascii_lowercase();
+        let relative = match name.len() {
+            1 => Path::new("1").join(&name),
+            2 => Path::new("2").join(&name),
+            3 => Path::new("3").join(&name[..1]).join(&name),
+            _ => Path::new(&name[..2]).join(&name[2..4]).join(&name),
+        };
+        self.cache_dirs
+            .iter()
+            .filter_map(|dir| std::fs::read(dir.join(&relative)).ok())
+            .flat_map(|bytes| parse_index_cache(&bytes))
+            .collect()
+    }
+
+    fn entry(&self, name: &str, version: &Version) -> Option<IndexEntry> {
+        self.entries(name)
+            .into_iter()
+            .find(|entry| entry.vers == *version)
+    }
+}
+
+/// Licenses to use instead of the declared ones, read from a TOML file
+/// mapping crate names to license expressions, e.g. `openssl = "Apache-2.0"`.
+///
+/// An entry may also be a table recording the justification of the
+/// override, e.g. `ring = { license = "ISC", reason = "...", approved_by =
+/// "legal" }`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct LicenseOverrides {
+    pub licenses: BTreeMap<String, LicenseOverride>,
+}
+
+/// A single entry of [`LicenseOverrides`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "OverrideEntry")]
+pub struct LicenseOverride {
+    pub license: String,
+    pub reason: Option<String>,
+    pub approved_by: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OverrideEntry {
+    License(String),
+    Annotated {
+        license: String,
+        reason: Option<String>,
+        approved_by: Option<String>,
+    },
+}
+
+impl From<OverrideEntry> for LicenseOverride {
+    fn from(entry: OverrideEntry) -> Self {
+        match entry {
+            OverrideEntry::License(license) => Self {
+                license,
+                reason: None,
+                approved_by: None,
+            },
+            OverrideEntry::Annotated {
+                license,
+                reason,
+                approved_by,
+            } => Self {
+                license,
+                reason,
+                approved_by,
+            },
+        }
+    }
+}
+
+impl LicenseOverrides {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&LicenseOverride> {
+        self.licenses.get(name)
+    }
+
+    #[must_use]
+    pub fn license(&self, name: &str) -> Option<&str> {
+        self.get(name).map(|entry| entry.license.as_str())
+    }
+}
+
+/// Tags to attach to crates in the report, read from a TOML file mapping
+/// crate names to lists of tags, e.g. `serde = ["reviewed-2024", "core"]`.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(transparent)]
+pub struct CrateTags {
+    pub tags: BTreeMap<String, Vec<String>>,
+}
+
+impl CrateTags {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        Ok(toml::from_str(&std::fs::read_to_string(path)?)?)
+    }
+
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.tags.get(name).map(Vec::as_slice)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DenyConfig {
+    licenses: Option<LicensePolicy>,
+}
+
+/// License allow/deny rules, read from the `[licenses]` section of a
+/// `cargo-deny` configuration file.
+///
+/// Only the `allow`, `deny` and `exceptions` keys are supported, every other
+/// key (e.g. `confidence-threshold` or `clarify`) is ignored.
+#[derive(Debug, Default, Deserialize)]
+pub struct LicensePolicy {
+    #[serde(default)]
+    pub allow: Vec<AllowedLicense>,
+    #[serde(default)]
+    pub deny: Vec<String>,
+    #[serde(default)]
+    pub exceptions: Vec<LicenseException>,
+}
+
+/// An entry of the `allow` list of a [`LicensePolicy`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum AllowedLicense {
+    /// A license allowed for every crate.
+    License(String),
+    /// A license only allowed for the listed crates, e.g.
+    /// `{ license = "GPL-3.0", crates = ["our-gpl-tool@1"] }`.
+    Scoped {
+        license: String,
+        crates: Vec<String>,
+    },
+}
+
+/// Additional licenses allowed for a single crate.
+#[derive(Debug, Deserialize)]
+pub struct LicenseException {
+    #[serde(alias = "crate")]
+    pub name: String,
+    pub allow: Vec<String>,
+}
+
+/// A crate name, optionally followed by `@` and a version requirement, such
+/// as `adler32` or `adler32@<2`.
+#[derive(Debug)]
+struct CrateSpec<'a> {
+    name: &'a str,
+    version: Option<semver::VersionReq>,
+}
+
+impl<'a> CrateSpec<'a> {
+    fn parse(spec: &'a str) -> Result<Self> {
+        let Some((name, version)) = spec.split_once('@') else {
+            return Ok(Self {
+                name: spec,
+                version: None,
+            });
+        };
+        let version = semver::VersionReq::parse(version).map_err(|source| {
+            CargoLicenseError::PolicyCrate {
+                spec: spec.to_owned(),
+                source,
+            }
+        })?;
+        Ok(Self {
+            name,
+            version: Some(version),
+        })
+    }
+
+    fn matches(&self, dependency: &DependencyDetails) -> bool {
+        self.name == dependency.name
+            && self
+                .version
+                .as_ref()
+                .map_or(true, |version| version.matches(&dependency.version))
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum PolicyRule {
+    Unlicensed,
+    InvalidExpression,
+    Denied,
+    NotAllowed,
+    ElectionRequired,
+    NotOsiApproved,
+    NotFsfLibre,
+    DeprecatedLicense,
+}
+
+impl fmt::Display for PolicyRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            PolicyRule::Unlicensed => "no SPDX license expression",
+            PolicyRule::InvalidExpression => "invalid SPDX license expression",
+            PolicyRule::Denied => "license is explicitly denied",
+            PolicyRule::NotAllowed => "license is not allowed",
+            PolicyRule::ElectionRequired => "no license has been elected among the choices",
+            PolicyRule::NotOsiApproved => "license is not OSI-approved",
+            PolicyRule::NotFsfLibre => "license is not FSF free/libre",
+            PolicyRule::DeprecatedLicense => "license uses a deprecated SPDX identifier",
+        })
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PolicyViolation {
+    #[serde(rename = "crate")]
+    pub name: String,
+    pub version: Version,
+    pub license: Option<String>,
+    pub rule: PolicyRule,
+}
+
+/// Returns `true` if `license` lets the licensee choose between licenses,
+/// i.e. it contains an `OR` operator.
+#[must_use]
+pub fn offers_license_choice(license: &str) -> bool {
+    spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).is_ok_and(|expression| {
+        expression.iter().any(|node| {
+            matches!(
+                node,
+                spdx::expression::ExprNode::Op(spdx::expression::Operator::Or)
+            )
+        })
+    })
+}
+
+/// Reports every crate offering a license choice. Recording the elected
+/// license as an override resolves the choice.
+#[must_use]
+pub fn check_elections(dependencies: &[DependencyDetails]) -> Vec<PolicyViolation> {
+    dependencies
+        .iter()
+        .filter(|dependency| {
+            dependency
+                .license
+                .as_deref()
+                .is_some_and(offers_license_choice)
+        })
+        .map(|dependency| PolicyViolation {
+            name: dependency.name.clone(),
+            version: dependency.version.clone(),
+            license: dependency.license.clone(),
+            rule: PolicyRule::ElectionRequired,
+        })
+        .collect()
+}
+
+/// Reports every crate whose license is not OSI-approved. An `OR`
+/// expression passes if any of its choices is OSI-approved, unless every
+/// choice must be in the worst case.
+#[must_use]
+pub fn check_osi_approved(
+    dependencies: &[DependencyDetails],
+    interpretation: Interpretation,
+) -> Vec<PolicyViolation> {
+    check_license_ids(
+        dependencies,
+        interpretation,
+        PolicyRule::NotOsiApproved,
+        spdx::LicenseId::is_osi_approved,
+    )
+}
+
+/// Reports every crate whose license is not FSF free/libre, with `OR`
+/// expressions handled as in [`check_osi_approved`].
+#[must_use]
+pub fn check_fsf_libre(
+    dependencies: &[DependencyDetails],
+    interpretation: Interpretation,
+) -> Vec<PolicyViolation> {
+    check_license_ids(
+        dependencies,
+        interpretation,
+        PolicyRule::NotFsfLibre,
+        spdx::LicenseId::is_fsf_free_libre,
+    )
+}
+
+/// Reports every crate whose license is missing, invalid or fails `rule`
+/// because its license ids don't have the property `accepted`.
+fn check_license_ids(
+    dependencies: &[DependencyDetails],
+    interpretation: Interpretation,
+    rule: PolicyRule,
+    accepted: fn(spdx::LicenseId) -> bool,
+) -> Vec<PolicyViolation> {
+    let accepted = |req: &spdx::LicenseReq| req.license.id().is_some_and(accepted);
+    dependencies
+        .iter()
+        .filter_map(|dependency| {
+            let rule = match &dependency.license {
+                None => PolicyRule::Unlicensed,
+                Some(license) => {
+                    match spdx::Expression::parse_mode(license, spdx::ParseMode::LAX) {
+                        Err(_) => PolicyRule::InvalidExpression,
+                        Ok(expression) => {
+                            let satisfied = match interpretation {
+                                Interpretation::BestCase => expression.evaluate(accepted),
+                                Interpretation::WorstCase => {
+                                    expression.requirements().all(|req| accepted(&req.req))
+                                }
+                            };
+                            if satisfied {
+                                return None;
+                            }
+                            rule
+                        }
+                    }
+                }
+            };
+            Some(PolicyViolation {
+                name: dependency.name.clone(),
+                version: dependency.version.clone(),
+                license: dependency.license.clone(),
+                rule,
+            })
+        })
+        .collect()
+}
+
+/// Reports every crate whose license uses a deprecated SPDX identifier,
+/// such as `GPL-3.0` instead of `GPL-3.0-only`.
+#[must_use]
+pub fn check_deprecated(dependencies: &[DependencyDetails]) -> Vec<PolicyViolation> {
+    // Parsed expressions store `GPL-3.0-only` as `GPL-3.0`, so look at the
+    // identifiers as written instead.
+    let deprecated = |license: &str| {
+        spdx::lexer::Lexer::new_mode(license, spdx::ParseMode::LAX).any(|token| {
+            matches!(
+                token,
+                Ok(spdx::lexer::LexerToken {
+                    token: spdx::lexer::Token::Spdx(id),
+                    ..
+                }) if id.is_deprecated()
+            )
+        })
+    };
+    dependencies
+        .iter()
+        .filter(|dependency| dependency.license.as_deref().is_some_and(deprecated))
+        .map(|dependency| PolicyViolation {
+            name: dependency.name.clone(),
+            version: dependency.version.clone(),
+            license: dependency.license.clone(),
+            rule: PolicyRule::DeprecatedLicense,
+        })
+        .collect()
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PolicyStatus {
+    Pass,
+    Fail,
+}
+
+/// The machine-readable result of a license policy check.
+#[derive(Debug, Serialize, Clone)]
+pub struct PolicyReport<'a> {
+    pub status: PolicyStatus,
+    pub violations: &'a [PolicyViolation],
+}
+
+impl<'a> PolicyReport<'a> {
+    #[must_use]
+    pub fn new(violations: &'a [PolicyViolation]) -> Self {
+        let status = if violations.is_empty() {
+            PolicyStatus::Pass
+        } else {
+            PolicyStatus::Fail
+        };
+        Self { status, violations }
+    }
+}
+
+fn parse_licensees(licenses: &[String]) -> Result<Vec<spdx::Licensee>> {
+    licenses
+        .iter()
+        .map(|license| {
+            spdx::Licensee::parse(license).map_err(|source| CargoLicenseError::PolicyLicense {
+                license: license.clone(),
+                source,
+            })
+        })
+        .collect()
+}
+
+impl LicensePolicy {
+    pub fn from_deny_config(path: &Path) -> Result<Self> {
+        let config: DenyConfig = toml::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(config.licenses.unwrap_or_default())
+    }
+
+    pub fn check(&self, dependencies: &[DependencyDetails]) -> Result<Vec<PolicyViolation>> {
+        self.check_with(dependencies, Interpretation::BestCase)
+    }
+
+    /// Like [`LicensePolicy::check`], but in the worst case every license of
+    /// an `OR` expression must be allowed.
+    pub fn check_with(
+        &self,
+        dependencies: &[DependencyDetails],
+        interpretation: Interpretation,
+    ) -> Result<Vec<PolicyViolation>> {
+        let allow = parse_licensees(
+            &self
+                .allow
+                .iter()
+                .filter_map(|entry| match entry {
+                    AllowedLicense::License(license) => Some(license.clone()),
+                    AllowedLicense::Scoped { .. } => None,
+                })
+                .collect::<Vec<_>>(),
+        )?;
+        let deny = parse_licensees(&self.deny)?;
+        // Every exception and scoped allow entry as the crates it applies to
+        // and the licenses it allows for them.
+        let mut exceptions = Vec::new();
+        for exception in &self.exceptions {
+            exceptions.push((
+                vec![CrateSpec::parse(&exception.name)?],
+                parse_licensees(&exception.allow)?,
+            ));
+        }
+        for entry in &self.allow {
+            if let AllowedLicense::Scoped { license, crates } = entry {
+                exceptions.push((
+                    crates
+                        .iter()
+                        .map(|spec| CrateSpec::parse(spec))
+                        .collect::<Result<_>>()?,
+                    parse_licensees(std::slice::from_ref(license))?,
+                ));
+            }
+        }
+
+        let mut violations = Vec::new();
+        for dependency in dependencies {
+            let rule = match &dependency.license {
+                None => Some(PolicyRule::Unlicensed),
+                Some(license) => {
+                    match spdx::Expression::parse_mode(license, spdx::ParseMode::LAX) {
+                        Err(_) => Some(PolicyRule::InvalidExpression),
+                        Ok(expression) => {
+                            let denied =
+                                |req: &spdx::LicenseReq| deny.iter().any(|l| l.satisfies(req));
+                            let allowed = |req: &spdx::LicenseReq| {
+                                allow.iter().any(|l| l.satisfies(req))
+                                    || exceptions
+                                        .iter()
+                                        .filter(|(specs, _)| {
+                                            specs.iter().any(|spec| spec.matches(dependency))
+                                        })
+                                        .any(|(_, allow)| allow.iter().any(|l| l.satisfies(req)))
+                            };
+                            let acceptable = |req: &spdx::LicenseReq| allowed(req) && !denied(req);
+                            let satisfied = match interpretation {
+                                Interpretation::BestCase => expression.evaluate(acceptable),
+                                Interpretation::WorstCase => {
+                                    expression.requirements().all(|req| acceptable(&req.req))
+                                }
+                            };
+                            if satisfied {
+                                None
+                            } else if expression.requirements().any(|req| denied(&req.req)) {
+                                Some(PolicyRule::Denied)
+                            } else {
+                                Some(PolicyRule::NotAllowed)
+                            }
+                        }
+                    }
+                }
+            };
+            if let Some(rule) = rule {
+                violations.push(PolicyViolation {
+                    name: dependency.name.clone(),
+                    version: dependency.version.clone(),
+                    license: dependency.license.clone(),
+                    rule,
+                });
+            }
+        }
+        Ok(violations)
+    }
+}
+
+/// Reads glob patterns of crate names from a file, one per line. Blank lines
+/// and lines starting with `#` are ignored.
+pub fn read_exclude_file(path: &Path) -> Result<Vec<String>> {
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_owned)
+        .collect())
+}
+
+fn build_glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// Number of crates above which a progress bar is shown automatically when
+/// stderr is a terminal.
+const PROGRESS_THRESHOLD: usize = 500;
+
+fn progress_bar(show: bool, len: usize) -> ProgressBar {
+    let auto = io::stderr().is_terminal() && len > PROGRESS_THRESHOLD;
+    if !show && !auto {
+        return ProgressBar::hidden();
+    }
+    ProgressBar::new(len as u64).with_style(
+        ProgressStyle::with_template("{prefix:>12.green.bold} [{bar:40}] {pos}/{len}")
+            .expect("valid progress template")
+            .progress_chars("=> "),
+    )
+}
+
+/// The order of the crates in the report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// By name and version.
+    #[default]
+    Name,
+    /// Every crate before its dependencies, starting from the roots. Crates
+    /// without an order between them, e.g. in a dependency cycle, are sorted
+    /// by name and version.
+    Topological,
+}
+
+/// How the `license_file` of every crate is reported.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LicenseFilePaths {
+    /// As declared in the crate's manifest, relative to its directory.
+    #[default]
+    Raw,
+    Absolute,
+    /// Relative to the workspace root, or absolute for crates outside of it.
+    WorkspaceRelative,
+}
+
+impl LicenseFilePaths {
+    fn resolve(self, path: &Utf8Path, workspace_root: &Utf8Path) -> Utf8PathBuf {
+        match self {
+            LicenseFilePaths::Raw | LicenseFilePaths::Absolute => path.to_owned(),
+            LicenseFilePaths::WorkspaceRelative => {
+                path.strip_prefix(workspace_root).unwrap_or(path).to_owned()
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct GetDependenciesOpt {
+    pub avoid_dev_deps: bool,
+    pub avoid_build_deps: bool,
+    pub avoid_proc_macros: bool,
+    pub direct_deps_only: bool,
+    pub root_only: bool,
+    pub no_root: bool,
+    /// Leave out packages with `publish = false`.
+    pub ignore_private: bool,
+    /// Only report packages reachable exclusively through build dependencies.
+    pub build_deps_only: bool,
+    /// Canonicalize and deduplicate the authors of every crate.
+    pub dedupe_authors: bool,
+    /// Joins the authors of every crate instead of `|`.
+    pub author_separator: Option<String>,
+    /// Canonicalize the repository URL of every crate.
+    pub normalize_repos: bool,
+    /// Read the license files of non-path dependencies from this `cargo
+    /// vendor` directory.
+    pub vendor_dir: Option<Utf8PathBuf>,
+    /// Follow exactly these dependency kinds, overriding `avoid_dev_deps` and
+    /// `avoid_build_deps`.
+    pub dep_kinds: Option<Vec<DependencyKind>>,
+    /// Always show a progress bar on stderr, regardless of the crate count.
+    pub progress: bool,
+    /// Only report the dependencies of this workspace member.
+    pub package: Option<String>,
+    /// Workspace members to leave out of the roots, along with the crates
+    /// only they depend on.
+    pub exclude_workspace_members: Vec<String>,
+    /// Glob patterns of crate names to leave out of the report.
+    pub exclude: Vec<String>,
+    /// Glob patterns of licenses to restrict the report to, if any.
+    pub include_license_match: Vec<String>,
+    /// Glob patterns of licenses to leave out of the report.
+    pub exclude_license_match: Vec<String>,
+    /// Licenses whose crates are left out of the report, if their license
+    /// expression can be satisfied with these licenses alone.
+    pub exclude_license: Vec<String>,
+    /// Only follow dependencies up to this many levels below the roots.
+    pub max_depth: Option<usize>,
+    /// Count the dependents of every crate.
+    pub include_graph_stats: bool,
+    /// Mark every crate as a direct or transitive dependency.
+    pub include_transitive_count: bool,
+    /// Report the dependency kinds through which every crate is reachable.
+    pub include_kinds: bool,
+    /// Report the shortest path from a root to every crate.
+    pub include_dependency_paths: bool,
+    /// Only report crates reachable through one of these dependency kinds.
+    pub kinds: Vec<DependencyKind>,
+    /// Licenses replacing the declared ones of the named crates.
+    pub overrides: LicenseOverrides,
+    /// Tags attached to the named crates.
+    pub tags: CrateTags,
+    /// Look up whether crates.io dependencies are yanked in cargo's local
+    /// registry index cache.
+    pub check_yanked: bool,
+    /// Flag crates.io versions published before this `YYYY-MM-DD` date, using
+    /// cargo's local registry index cache.
+    pub since: Option<String>,
+    /// How the `license_file` of every crate is reported.
+    pub license_file_paths: LicenseFilePaths,
+    /// The order of the reported crates.
+    pub sort_by: SortOrder,
+    /// Compute the SHA-256 of every crate's `license-file`.
+    pub hash_license_files: bool,
+    /// Print the duration of every phase to stderr.
+    pub timings: bool,
+    /// Called with the duration of every phase instead of printing it.
+    pub on_timing: Option<fn(&str, Duration)>,
+    /// Report the resolved features of every crate.
+    pub include_features: bool,
+    /// Keep the terms of license expressions in their declared order
+    /// instead of sorting them.
+    pub no_normalize_sort: bool,
+    /// Report the edition and MSRV of every crate.
+    pub include_metadata: bool,
+    /// Keep a single crate per name and version.
+    pub unique: bool,
+}
+
+/// Builder for [`GetDependenciesOpt`], created with
+/// [`GetDependenciesOpt::builder`].
+///
+/// ```
+/// let opt = cargo_license::GetDependenciesOpt::builder()
+///     .avoid_dev_deps(true)
+///     .exclude(["windows-*"])
+///     .build();
+/// assert!(opt.avoid_dev_deps);
+/// ```
+#[derive(Default)]
+pub struct GetDependenciesOptBuilder(GetDependenciesOpt);
+
+impl GetDependenciesOptBuilder {
+    /// Skip development dependencies.
+    #[must_use]
+    pub fn avoid_dev_deps(mut self, avoid_dev_deps: bool) -> Self {
+        self.0.avoid_dev_deps = avoid_dev_deps;
+        self
+    }
+
+    /// Skip build dependencies.
+    #[must_use]
+    pub fn avoid_build_deps(mut self, avoid_build_deps: bool) -> Self {
+        self.0.avoid_build_deps = avoid_build_deps;
+        self
+    }
+
+    /// Skip proc-macro crates and their dependencies.
+    #[must_use]
+    pub fn avoid_proc_macros(mut self, avoid_proc_macros: bool) -> Self {
+        self.0.avoid_proc_macros = avoid_proc_macros;
+        self
+    }
+
+    /// Only report the direct dependencies of the roots.
+    #[must_use]
+    pub fn direct_deps_only(mut self, direct_deps_only: bool) -> Self {
+        self.0.direct_deps_only = direct_deps_only;
+        self
+    }
+
+    /// Only report the root packages.
+    #[must_use]
+    pub fn root_only(mut self, root_only: bool) -> Self {
+        self.0.root_only = root_only;
+        self
+    }
+
+    /// Leave the root packages out of the report.
+    #[must_use]
+    pub fn no_root(mut self, no_root: bool) -> Self {
+        self.0.no_root = no_root;
+        self
+    }
+
+    /// Only report packages reachable exclusively through build
+    /// dependencies.
+    #[must_use]
+    pub fn build_deps_only(mut self, build_deps_only: bool) -> Self {
+        self.0.build_deps_only = build_deps_only;
+        self
+    }
+
+    /// Canonicalize and deduplicate the authors of every crate, see
+    /// [`normalize_authors`].
+    #[must_use]
+    pub fn dedupe_authors(mut self, dedupe_authors: bool) -> Self {
+        self.0.dedupe_authors = dedupe_authors;
+        self
+    }
+
+    /// Join the authors of every crate with `separator` instead of `|`.
+    #[must_use]
+    pub fn author_separator(mut self, separator: impl Into<String>) -> Self {
+        self.0.author_separator = Some(separator.into());
+        self
+    }
+
+    /// Read the license files of non-path dependencies from a `cargo vendor`
+    /// directory.
+    #[must_use]
+    pub fn vendor_dir(mut self, vendor_dir: Option<Utf8PathBuf>) -> Self {
+        self.0.vendor_dir = vendor_dir;
+        self
+    }
+
+    /// Canonicalize the repository URL of every crate, see
+    /// [`normalize_repository`].
+    #[must_use]
+    pub fn normalize_repos(mut self, normalize_repos: bool) -> Self {
+        self.0.normalize_repos = normalize_repos;
+        self
+    }
+
+    /// Leave packages with `publish = false` out of the report.
+    #[must_use]
+    pub fn ignore_private(mut self, ignore_private: bool) -> Self {
+        self.0.ignore_private = ignore_private;
+        self
+    }
+
+    /// Always show a progress bar on stderr.
+    #[must_use]
+    pub fn progress(mut self, progress: bool) -> Self {
+        self.0.progress = progress;
+        self
+    }
+
+    /// Look up whether crates.io dependencies are yanked.
+    #[must_use]
+    pub fn check_yanked(mut self, check_yanked: bool) -> Self {
+        self.0.check_yanked = check_yanked;
+        self
+    }
+
+    /// Flag crates.io versions published before a `YYYY-MM-DD` date.
+    #[must_use]
+    pub fn since(mut self, since: Option<String>) -> Self {
+        self.0.since = since;
+        self
+    }
+
+    /// Count the dependents of every crate.
+    #[must_use]
+    pub fn include_graph_stats(mut self, include_graph_stats: bool) -> Self {
+        self.0.include_graph_stats = include_graph_stats;
+        self
+    }
+
+    /// Order the crates by name, or dependencies before their dependents.
+    #[must_use]
+    pub fn sort_by(mut self, sort_by: SortOrder) -> Self {
+        self.0.sort_by = sort_by;
+        self
+    }
+
+    /// Report the `license_file` of every crate as declared, absolute or
+    /// relative to the workspace root.
+    #[must_use]
+    pub fn license_file_paths(mut self, license_file_paths: LicenseFilePaths) -> Self {
+        self.0.license_file_paths = license_file_paths;
+        self
+    }
+
+    /// Compute the SHA-256 of every crate's `license-file`.
+    #[must_use]
+    pub fn hash_license_files(mut self, hash_license_files: bool) -> Self {
+        self.0.hash_license_files = hash_license_files;
+        self
+    }
+
+    /// Print the duration of every phase to stderr.
+    #[must_use]
+    pub fn timings(mut self, timings: bool) -> Self {
+        self.0.timings = timings;
+        self
+    }
+
+    /// Report the duration of every phase to `on_timing` instead of stderr.
+    #[must_use]
+    pub fn on_timing(mut self, on_timing: fn(&str, Duration)) -> Self {
+        self.0.on_timing = Some(on_timing);
+        self
+    }
+
+    /// Report the resolved features of every crate.
+    #[must_use]
+    pub fn include_features(mut self, include_features: bool) -> Self {
+        self.0.include_features = include_features;
+        self
+    }
+
+    /// Report the shortest path from a root to every crate.
+    #[must_use]
+    pub fn include_dependency_paths(mut self, include_dependency_paths: bool) -> Self {
+        self.0.include_dependency_paths = include_dependency_paths;
+        self
+    }
+
+    /// Report the dependency kinds through which every crate is reachable.
+    #[must_use]
+    pub fn include_kinds(mut self, include_kinds: bool) -> Self {
+        self.0.include_kinds = include_kinds;
+        self
+    }
+
+    /// Only report crates reachable through one of these dependency kinds.
+    #[must_use]
+    pub fn kinds(mut self, kinds: impl IntoIterator<Item = DependencyKind>) -> Self {
+        self.0.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    /// Mark every crate as a direct or transitive dependency.
+    #[must_use]
+    pub fn include_transitive_count(mut self, include_transitive_count: bool) -> Self {
+        self.0.include_transitive_count = include_transitive_count;
+        self
+    }
+
+    /// Keep a single crate per name and version.
+    #[must_use]
+    pub fn unique(mut self, unique: bool) -> Self {
+        self.0.unique = unique;
+        self
+    }
+
+    /// Report the edition and MSRV of every crate.
+    #[must_use]
+    pub fn include_metadata(mut self, include_metadata: bool) -> Self {
+        self.0.include_metadata = include_metadata;
+        self
+    }
+
+    /// Keep the terms of license expressions in their declared order.
+    #[must_use]
+    pub fn no_normalize_sort(mut self, no_normalize_sort: bool) -> Self {
+        self.0.no_normalize_sort = no_normalize_sort;
+        self
+    }
+
+    /// Follow exactly these dependency kinds.
+    #[must_use]
+    pub fn dep_kinds(mut self, dep_kinds: impl IntoIterator<Item = DependencyKind>) -> Self {
+        self.0.dep_kinds = Some(dep_kinds.into_iter().collect());
+        self
+    }
+
+    /// Only report the dependencies of this workspace member.
+    #[must_use]
+    pub fn package(mut self, package: impl Into<String>) -> Self {
+        self.0.package = Some(package.into());
+        self
+    }
+
+    /// Leave these workspace members out of the roots, along with the crates
+    /// only they depend on.
+    #[must_use]
+    pub fn exclude_workspace_members<S: Into<String>>(
+        mut self,
+        members: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.0.exclude_workspace_members = members.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Leave crates whose name matches any of these glob patterns out of the
+    /// report.
+    #[must_use]
+    pub fn exclude<S: Into<String>>(mut self, patterns: impl IntoIterator<Item = S>) -> Self {
+        self.0.exclude.extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only report crates whose license matches any of these glob patterns.
+    #[must_use]
+    pub fn include_license_match<S: Into<String>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.0
+            .include_license_match
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leave crates whose license matches any of these glob patterns out of
+    /// the report.
+    #[must_use]
+    pub fn exclude_license_match<S: Into<String>>(
+        mut self,
+        patterns: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.0
+            .exclude_license_match
+            .extend(patterns.into_iter().map(Into::into));
+        self
+    }
+
+    /// Leave crates usable under these licenses alone out of the report.
+    #[must_use]
+    pub fn exclude_license<S: Into<String>>(
+        mut self,
+        licenses: impl IntoIterator<Item = S>,
+    ) -> Self {
+        self.0
+            .exclude_license
+            .extend(licenses.into_iter().map(Into::into));
+        self
+    }
+
+    /// Only follow dependencies up to this many levels below the roots.
+    #[must_use]
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.0.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Replace the declared licenses of the named crates.
+    #[must_use]
+    pub fn overrides(mut self, overrides: LicenseOverrides) -> Self {
+        self.0.overrides = overrides;
+        self
+    }
+
+    /// Attach tags to the named crates.
+    #[must_use]
+    pub fn tags(mut self, tags: CrateTags) -> Self {
+        self.0.tags = tags;
+        self
+    }
+
+    /// Finish the options, leaving every unset option at its default.
+    #[must_use]
+    pub fn build(self) -> GetDependenciesOpt {
+        self.0
+    }
+}
+
+impl GetDependenciesOpt {
+    #[must_use]
+    pub fn builder() -> GetDependenciesOptBuilder {
+        GetDependenciesOptBuilder::default()
+    }
+
+    fn includes_dep_kind(&self, kind: DependencyKind) -> bool {
+        if let Some(kinds) = &self.dep_kinds {
+            return kinds.contains(&kind);
+        }
+        kind == DependencyKind::Normal
+            || !self.avoid_dev_deps && kind == DependencyKind::Development
+            || !self.avoid_build_deps && kind == DependencyKind::Build
+    }
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningKind {
+    MissingDepKinds,
+    AmbiguousPrecedence,
+    InvalidLicense,
+    MissingLicense,
+    LicenseConsistency,
+    Yanked,
+    Stale,
+    RegistryIndexUnavailable,
+    IncompatibleLicenses,
+    UnreadableLicenseFile,
+    NotVendored,
+    DuplicateCrate,
+}
+
+/// A non-fatal problem found while collecting or checking dependencies.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub kind: WarningKind,
+    pub message: String,
+}
+
+impl Warning {
+    #[must_use]
+    pub fn new(kind: WarningKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// Reports how long a phase took if timings are enabled, to stderr unless
+/// `on_timing` is set.
+fn report_timing(opt: &GetDependenciesOpt, phase: &str, start: Instant) {
+    if !opt.timings {
+        return;
+    }
+    match opt.on_timing {
+        Some(on_timing) => on_timing(phase, start.elapsed()),
+        None => eprintln!("timing: {phase}: {:.2?}", start.elapsed()),
+    }
+}
+
+/// Returns the crates whose license mentions a license satisfying
+/// `licensee`, e.g. the crates a copyleft license comes from.
+#[must_use]
+pub fn who_uses<'a>(
+    dependencies: &'a [DependencyDetails],
+    licensee: &spdx::Licensee,
+) -> Vec<&'a DependencyDetails> {
+    dependencies
+        .iter()
+        .filter(|dependency| {
+            dependency.license.as_deref().is_some_and(|license| {
+                spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).is_ok_and(
+                    |expression| {
+                        expression
+                            .requirements()
+                            .any(|requirement| licensee.satisfies(&requirement.req))
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+/// Returns the directory of a crate vendored in `vendor_dir` by `cargo
+/// vendor`: `name-version`, as created for all but one version of a crate or
+/// with `--versioned-dirs`, or else `name`.
+fn vendored_dir(vendor_dir: &Utf8Path, name: &str, version: &Version) -> Option<Utf8PathBuf> {
+    [
+        vendor_dir.join(format!("{name}-{version}")),
+        vendor_dir.join(name),
+    ]
+    .into_iter()
+    .find(|dir| dir.join("Cargo.toml").is_file())
+}
+
+/// Returns `true` if `license` can be satisfied with the `licensees` alone.
+fn satisfied_by(license: &str, licensees: &[spdx::Licensee]) -> bool {
+    !licensees.is_empty()
+        && spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).is_ok_and(|expression| {
+            expression.evaluate(|req| licensees.iter().any(|licensee| licensee.satisfies(req)))
+        })
+}
+
+/// Returns `true` if cargo refused to create or update `Cargo.lock` because of
+/// `--locked` or `--frozen`.
+fn is_lockfile_update_error(stderr: &str) -> bool {
+    stderr.contains("lock file")
+        && (stderr.contains("--locked was passed") || stderr.contains("--frozen was passed"))
+}
+
+/// Like [`get_dependencies_from_cargo_lock`], but returns the warnings
+/// instead of printing them to stderr.
+pub fn get_dependencies_with_warnings(
+    metadata_command: MetadataCommand,
+    opt: GetDependenciesOpt,
+) -> Result<(Vec<DependencyDetails>, Vec<Warning>)> {
+    let mut warnings = Vec::new();
+    let spinner = if opt.progress {
+        ProgressBar::new_spinner().with_message("Resolving dependencies")
+    } else {
+        ProgressBar::hidden()
+    };
+    spinner.enable_steady_tick(Duration::from_millis(100));
+    let start = Instant::now();
+    let metadata = metadata_command.exec();
+    spinner.finish_and_clear();
+    let metadata = metadata.map_err(|err| match &err {
+        cargo_metadata::Error::CargoMetadata { stderr } if is_lockfile_update_error(stderr) => {
+            CargoLicenseError::LockfileUpdate(err)
+        }
+        _ => err.into(),
+    })?;
+    report_timing(&opt, "cargo metadata", start);
+    let start = Instant::now();
+
+    let exclude = build_glob_set(&opt.exclude)?;
+    let include_license = build_glob_set(&opt.include_license_match)?;
+    let exclude_license = build_glob_set(&opt.exclude_license_match)?;
+    let excluded_licensees = opt
+        .exclude_license
+        .iter()
+        .map(|license| {
+            spdx::Licensee::parse(license).map_err(|source| CargoLicenseError::ExcludedLicense {
+                license: license.clone(),
+                source,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let roots = get_roots(&metadata, &opt)?;
+    let node_name_filter = get_node_name_filter(&roots, &opt);
+    let proc_macro_exclusions = get_proc_macro_node_names(&metadata, &opt);
+
+    let (connected, dependents, ranks, kinds, parents, direct) = {
+        let resolve = metadata.resolve.as_ref().expect("missing `resolve`");
+
+        let deps = resolve
+            .nodes
+            .iter()
+            .map(|Node { id, deps, .. }| (id, deps))
+            .collect::<HashMap<_, _>>();
+
+        let missing_dep_kinds = deps
+            .values()
+            .flat_map(|d| d.iter())
+            .any(|NodeDep { dep_kinds, .. }| dep_kinds.is_empty());
+
+        if missing_dep_kinds && opt.avoid_dev_deps {
+            warnings.push(Warning::new(
+                WarningKind::MissingDepKinds,
+                "Cargo 1.41+ is required for `--avoid-dev-deps`",
+            ));
+        }
+        if missing_dep_kinds && opt.avoid_build_deps {
+            warnings.push(Warning::new(
+                WarningKind::MissingDepKinds,
+                "Cargo 1.41+ is required for `--avoid-build-deps`",
+            ));
+        }
+        if missing_dep_kinds && opt.dep_kinds.is_some() {
+            warnings.push(Warning::new(
+                WarningKind::MissingDepKinds,
+                "Cargo 1.41+ is required for `--dep-kinds`",
+            ));
         }
 
         let neighbors = |package_id: &PackageId| {
@@ -212,62 +2978,830 @@ pub fn get_dependencies_from_cargo_lock(
                 .iter()
                 .filter(|NodeDep { dep_kinds, .. }| {
                     missing_dep_kinds
-                        || dep_kinds.iter().any(|DepKindInfo { kind, .. }| {
-                            *kind == DependencyKind::Normal
-                                || !opt.avoid_dev_deps && *kind == DependencyKind::Development
-                                || !opt.avoid_build_deps && *kind == DependencyKind::Build
-                        })
+                        || dep_kinds
+                            .iter()
+                            .any(|DepKindInfo { kind, .. }| opt.includes_dep_kind(*kind))
                 })
                 .map(|NodeDep { pkg, .. }| pkg)
         };
 
+        // Walk breadth-first so that every package is first reached through
+        // its shortest path, which `max_depth` is measured against.
         let mut connected = HashSet::new();
-        let stack = &mut if let Some(root) = &resolve.root {
-            vec![root]
+        let mut parents = HashMap::new();
+        let queue = &mut roots
+            .iter()
+            .map(|root| (&root.id, 0, None))
+            .collect::<VecDeque<_>>();
+        while let Some((package_id, depth, parent)) = queue.pop_front() {
+            if !connected.insert(package_id) {
+                continue;
+            }
+            if let Some(parent) = parent {
+                parents.insert(package_id, parent);
+            }
+            if opt.max_depth.map_or(true, |max| depth < max) {
+                queue.extend(neighbors(package_id).map(|pkg| (pkg, depth + 1, Some(package_id))));
+            }
+        }
+
+        // Before `--build-deps-only` drops the roots and the runtime path the
+        // build dependencies are reached through.
+        let kinds = if opt.include_kinds || !opt.kinds.is_empty() {
+            reached_kinds(&roots, &deps, &connected, &opt)
+        } else {
+            HashMap::new()
+        };
+
+        if opt.build_deps_only {
+            // Drop every package reachable without going through a build
+            // dependency edge.
+            let mut runtime = HashSet::new();
+            let stack = &mut roots.iter().map(|root| &root.id).collect::<Vec<_>>();
+            while let Some(package_id) = stack.pop() {
+                if runtime.insert(package_id) {
+                    stack.extend(
+                        deps[package_id]
+                            .iter()
+                            .filter(|NodeDep { dep_kinds, .. }| {
+                                dep_kinds.iter().any(|DepKindInfo { kind, .. }| {
+                                    *kind != DependencyKind::Build && opt.includes_dep_kind(*kind)
+                                })
+                            })
+                            .map(|NodeDep { pkg, .. }| pkg),
+                    );
+                }
+            }
+            connected.retain(|package_id| !runtime.contains(package_id));
+        }
+
+        let mut dependents = HashMap::<&PackageId, usize>::new();
+        for package_id in &connected {
+            for dependency in neighbors(package_id).filter(|pkg| connected.contains(pkg)) {
+                *dependents.entry(dependency).or_default() += 1;
+            }
+        }
+        let ranks = if opt.sort_by == SortOrder::Topological {
+            let edges = connected
+                .iter()
+                .map(|package_id| {
+                    let dependencies = neighbors(package_id)
+                        .filter(|pkg| connected.contains(pkg))
+                        .collect();
+                    (*package_id, dependencies)
+                })
+                .collect();
+            topological_ranks(&metadata, &edges)
         } else {
-            metadata.workspace_members.iter().collect()
+            HashMap::new()
         };
-        while let Some(package_id) = stack.pop() {
-            if connected.insert(package_id) {
-                stack.extend(neighbors(package_id));
+
+        // Matched by id, since another version of a direct dependency may be
+        // pulled in transitively.
+        let direct = roots
+            .iter()
+            .flat_map(|root| neighbors(&root.id))
+            .collect::<HashSet<_>>();
+
+        (connected, dependents, ranks, kinds, parents, direct)
+    };
+
+    let packages = metadata
+        .packages
+        .iter()
+        .filter(|p| connected.contains(&p.id))
+        .filter(|p| node_name_filter.is_empty() || node_name_filter.contains(&p.name))
+        .filter(|p| !proc_macro_exclusions.contains(&p.name))
+        .filter(|p| !opt.no_root || !metadata.workspace_members.contains(&p.id))
+        .filter(|p| !opt.ignore_private || !p.publish.as_ref().is_some_and(Vec::is_empty))
+        .filter(|p| !exclude.is_match(&p.name))
+        .filter(|p| {
+            opt.kinds.is_empty()
+                || opt.kinds.iter().any(|kind| {
+                    kinds
+                        .get(&p.id)
+                        .is_some_and(|kinds| kinds.contains(dependency_kind_name(*kind)))
+                })
+        })
+        .collect::<Vec<_>>();
+
+    let features = metadata
+        .resolve
+        .iter()
+        .flat_map(|resolve| &resolve.nodes)
+        .filter(|_| opt.include_features)
+        .map(|Node { id, features, .. }| (id, features))
+        .collect::<HashMap<_, _>>();
+
+    let index = if opt.check_yanked || opt.since.is_some() {
+        let index = RegistryIndex::open();
+        if index.is_none() {
+            warnings.push(Warning::new(
+                WarningKind::RegistryIndexUnavailable,
+                "cargo's registry index cache was not found, skipping the registry checks",
+            ));
+        }
+        index
+    } else {
+        None
+    };
+
+    report_timing(&opt, "dependency graph", start);
+    let start = Instant::now();
+    let progress = progress_bar(opt.progress, packages.len()).with_prefix("Collecting");
+    let mut detailed_dependencies = Vec::with_capacity(packages.len());
+    for package in progress.wrap_iter(packages.into_iter()) {
+        let author_separator = opt
+            .author_separator
+            .as_deref()
+            .unwrap_or(DEFAULT_AUTHOR_SEPARATOR);
+        let mut dependency = DependencyDetails::with_author_separator(package, author_separator);
+        if opt.include_graph_stats {
+            dependency.dependents = Some(dependents.get(&package.id).copied().unwrap_or(0));
+        }
+        if opt.no_normalize_sort {
+            dependency.license = package
+                .license
+                .as_deref()
+                .map(|license| normalize_with(license, false));
+        }
+        if opt.include_dependency_paths {
+            let mut path = vec![&package.id];
+            while let Some(parent) = parents.get(path[path.len() - 1]) {
+                path.push(parent);
+            }
+            dependency.dependency_path = Some(
+                path.into_iter()
+                    .rev()
+                    .map(|id| {
+                        let package = &metadata[id];
+                        format!("{} {}", package.name, package.version)
+                    })
+                    .collect(),
+            );
+        }
+        if opt.include_kinds {
+            dependency.kinds = Some(
+                kinds
+                    .get(&package.id)
+                    .into_iter()
+                    .flatten()
+                    .map(|&kind| kind.to_owned())
+                    .collect(),
+            );
+        }
+        if opt.include_transitive_count {
+            dependency.is_direct = Some(
+                direct.contains(&package.id) && !roots.iter().any(|root| root.id == package.id),
+            );
+        }
+        if opt.include_features {
+            dependency.features = Some(
+                features
+                    .get(&package.id)
+                    .map_or_else(Vec::new, |f| f.iter().map(ToString::to_string).collect()),
+            );
+        }
+        if opt.include_metadata {
+            dependency.edition = Some(package.edition.to_string());
+            dependency.rust_version = package.rust_version.as_ref().map(ToString::to_string);
+        }
+        if opt.dedupe_authors {
+            dependency.authors = dependency
+                .authors
+                .as_deref()
+                .map(|authors| normalize_authors(authors, author_separator));
+        }
+        if opt.normalize_repos {
+            dependency.repository = dependency.repository.as_deref().map(normalize_repository);
+        }
+        if let Some(entry) = opt.overrides.get(&package.name) {
+            dependency.license = Some(normalize_with(&entry.license, !opt.no_normalize_sort));
+            dependency.override_reason = entry.reason.clone();
+            dependency.override_approved_by = entry.approved_by.clone();
+        }
+        dependency.tags = opt.tags.get(&package.name).map(<[String]>::to_vec);
+        if let Some(vendor_dir) = opt
+            .vendor_dir
+            .as_deref()
+            .filter(|_| package.source.is_some())
+        {
+            match vendored_dir(vendor_dir, &package.name, &package.version) {
+                Some(dir) => {
+                    dependency.license_file_path =
+                        package.license_file.as_ref().map(|file| dir.join(file));
+                    dependency.manifest_path = dir.join("Cargo.toml");
+                }
+                None => warnings.push(Warning::new(
+                    WarningKind::NotVendored,
+                    format!(
+                        "`{} {}` was not found in the vendor directory `{vendor_dir}`",
+                        package.name, package.version
+                    ),
+                )),
+            }
+        }
+        if let Some(path) = dependency
+            .license_file_path
+            .as_ref()
+            .filter(|_| opt.hash_license_files)
+        {
+            match std::fs::read(path) {
+                Ok(contents) => {
+                    dependency.license_file_sha256 =
+                        Some(format!("{:x}", Sha256::digest(contents)));
+                }
+                Err(err) => warnings.push(Warning::new(
+                    WarningKind::UnreadableLicenseFile,
+                    format!(
+                        "failed to read the license file of `{} {}` at `{path}`: {err}",
+                        package.name, package.version
+                    ),
+                )),
+            }
+        }
+        if opt.license_file_paths != LicenseFilePaths::Raw {
+            if let Some(path) = &dependency.license_file_path {
+                let path = opt
+                    .license_file_paths
+                    .resolve(path, &metadata.workspace_root);
+                dependency.license_file = Some(path.into_string());
+            }
+        }
+        if let Some(index) = &index {
+            let is_crates_io = package.source.as_ref().is_some_and(|s| s.is_crates_io());
+            let entry = if is_crates_io {
+                index.entry(&package.name, &package.version)
+            } else {
+                None
+            };
+            if is_crates_io && entry.is_none() {
+                warnings.push(Warning::new(
+                    WarningKind::RegistryIndexUnavailable,
+                    format!(
+                        "`{} {}` was not found in cargo's registry index cache",
+                        package.name, package.version
+                    ),
+                ));
+            }
+            if let (Some(since), Some(entry)) = (&opt.since, &entry) {
+                match entry.pubtime.as_deref().and_then(|time| time.get(..10)) {
+                    Some(date) if date < since.as_str() => warnings.push(Warning::new(
+                        WarningKind::Stale,
+                        format!(
+                            "`{} {}` was published on {date}, before {since}",
+                            package.name, package.version
+                        ),
+                    )),
+                    Some(_) => {}
+                    None => warnings.push(Warning::new(
+                        WarningKind::RegistryIndexUnavailable,
+                        format!(
+                            "`{} {}` has no publish date in cargo's registry index cache",
+                            package.name, package.version
+                        ),
+                    )),
+                }
+            }
+            // The status of crates missing from the index is unknown.
+            if let Some(entry) = entry.as_ref().filter(|_| opt.check_yanked) {
+                if entry.yanked {
+                    warnings.push(Warning::new(
+                        WarningKind::Yanked,
+                        format!(
+                            "`{} {}` is yanked from crates.io",
+                            package.name, package.version
+                        ),
+                    ));
+                }
+                dependency.yanked = Some(entry.yanked);
+            }
+        }
+        detailed_dependencies.push(dependency);
+    }
+    progress.finish_and_clear();
+    // Crates without a license only match when no license is required.
+    detailed_dependencies.retain(|dependency| match &dependency.license {
+        Some(license) => {
+            (opt.include_license_match.is_empty() || include_license.is_match(license))
+                && !exclude_license.is_match(license)
+                && !satisfied_by(license, &excluded_licensees)
+        }
+        None => opt.include_license_match.is_empty(),
+    });
+    detailed_dependencies.sort_unstable();
+    if opt.sort_by == SortOrder::Topological {
+        let ranks = metadata
+            .packages
+            .iter()
+            .filter_map(|p| Some(((p.name.as_str(), &p.version), ranks.get(&p.id)?)))
+            .collect::<HashMap<_, _>>();
+        detailed_dependencies.sort_by_key(|d| {
+            ranks
+                .get(&(d.name.as_str(), &d.version))
+                .map_or(usize::MAX, |rank| **rank)
+        });
+    }
+    if opt.unique {
+        warnings.extend(dedupe_dependencies(&mut detailed_dependencies));
+    }
+    report_timing(&opt, "package details", start);
+    let start = Instant::now();
+
+    for dependency in &detailed_dependencies {
+        let package = format!("{} {}", dependency.name, dependency.version);
+        match &dependency.license {
+            Some(license) => {
+                if spdx::Expression::parse_mode(license, spdx::ParseMode::LAX).is_err() {
+                    warnings.push(Warning::new(
+                        WarningKind::InvalidLicense,
+                        format!(
+                            "license of `{package}` is not a valid SPDX expression: `{license}`"
+                        ),
+                    ));
+                } else if has_ambiguous_precedence(license) {
+                    warnings.push(Warning::new(
+                        WarningKind::AmbiguousPrecedence,
+                        format!(
+                            "license of `{package}` relies on implicit AND/OR precedence: \
+                             `{license}`; `AND` binds tighter than `OR`, consider adding parentheses"
+                        ),
+                    ));
+                }
+            }
+            None if dependency.license_file.is_none() => {
+                warnings.push(Warning::new(
+                    WarningKind::MissingLicense,
+                    format!("`{package}` has neither a license nor a license file"),
+                ));
+            }
+            None => {}
+        }
+    }
+    report_timing(&opt, "license checks", start);
+    Ok((detailed_dependencies, warnings))
+}
+
+pub fn get_dependencies_from_cargo_lock(
+    metadata_command: MetadataCommand,
+    opt: GetDependenciesOpt,
+) -> Result<Vec<DependencyDetails>> {
+    let (dependencies, warnings) = get_dependencies_with_warnings(metadata_command, opt)?;
+    for warning in warnings {
+        eprintln!("warning: {warning}");
+    }
+    Ok(dependencies)
+}
+
+/// Capacity of the standard output buffer, large enough to hold the TSV
+/// record of any crate so that every record reaches the pipe in one write.
+const STDOUT_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// Buffered standard output for the writers, which serialize straight into
+/// it instead of rendering the whole report to a string first.
+fn buffered_stdout() -> io::BufWriter<io::StdoutLock<'static>> {
+    io::BufWriter::with_capacity(STDOUT_BUFFER_CAPACITY, io::stdout().lock())
+}
+
+/// Renders the dependencies as tab-separated values with a header row.
+pub fn to_tsv_string(dependencies: &[DependencyDetails]) -> Result<String> {
+    let mut bytes = Vec::new();
+    write_tsv_to(&mut bytes, dependencies)?;
+    Ok(String::from_utf8(bytes).expect("TSV output is UTF-8"))
+}
+
+fn write_tsv_to(writer: impl io::Write, dependencies: &[DependencyDetails]) -> Result<()> {
+    let mut wtr = csv::WriterBuilder::new()
+        .delimiter(b'\t')
+        .quote_style(csv::QuoteStyle::Necessary)
+        .from_writer(writer);
+    // Every record needs the same columns, so fill the optional per-crate
+    // fields in for the other crates once any crate has them.
+    let has_reason = dependencies.iter().any(|d| d.override_reason.is_some());
+    let has_approved_by = dependencies
+        .iter()
+        .any(|d| d.override_approved_by.is_some());
+    let has_sha256 = dependencies.iter().any(|d| d.license_file_sha256.is_some());
+    let has_features = dependencies.iter().any(|d| d.features.is_some());
+    let has_tags = dependencies.iter().any(|d| d.tags.is_some());
+    let has_rust_version = dependencies.iter().any(|d| d.rust_version.is_some());
+    for dependency in dependencies {
+        let mut dependency = dependency.clone();
+        if has_reason {
+            dependency.override_reason.get_or_insert_with(String::new);
+        }
+        if has_approved_by {
+            dependency
+                .override_approved_by
+                .get_or_insert_with(String::new);
+        }
+        if has_sha256 {
+            dependency
+                .license_file_sha256
+                .get_or_insert_with(String::new);
+        }
+        if has_rust_version {
+            dependency.rust_version.get_or_insert_with(String::new);
+        }
+        // A sequence would spread over several columns, so join the features
+        // into a single one.
+        if has_features {
+            let features = dependency.features.unwrap_or_default().join(",");
+            dependency.features = Some(vec![features]);
+        }
+        if has_tags {
+            let tags = dependency.tags.unwrap_or_default().join(",");
+            dependency.tags = Some(vec![tags]);
+        }
+        if let Some(kinds) = &mut dependency.kinds {
+            *kinds = vec![kinds.join(",")];
+        }
+        if let Some(path) = &mut dependency.dependency_path {
+            *path = vec![path.join(" -> ")];
+        }
+        wtr.serialize(dependency)?;
+        // Flush every record so that a consumer reading the output live can
+        // process it line by line.
+        wtr.flush()?;
+    }
+    Ok(())
+}
+
+pub fn write_tsv(dependencies: &[DependencyDetails]) -> Result<()> {
+    write_tsv_to(buffered_stdout(), dependencies)
+}
+
+/// Renders the dependencies as a TOML array of tables under `dependency`.
+/// Fields without a value are omitted, since TOML has no null.
+pub fn to_toml_string(dependencies: &[DependencyDetails]) -> Result<String> {
+    #[derive(Serialize)]
+    struct TomlReport<'a> {
+        dependency: &'a [DependencyDetails],
+    }
+
+    Ok(toml::to_string(&TomlReport {
+        dependency: dependencies,
+    })?)
+}
+
+pub fn write_toml(dependencies: &[DependencyDetails]) -> Result<()> {
+    print!("{}", to_toml_string(dependencies)?);
+    Ok(())
+}
+
+/// Groups the dependencies by license, as in the grouped text output. Crates
+/// without a license are grouped under `Custom License File` if they have a
+/// license file, and `N/A` otherwise.
+#[must_use]
+pub fn group_by_license(
+    dependencies: Vec<DependencyDetails>,
+) -> BTreeMap<String, Vec<DependencyDetails>> {
+    let mut table: BTreeMap<String, Vec<DependencyDetails>> = BTreeMap::new();
+    for dependency in dependencies {
+        let license = dependency.license.clone().unwrap_or_else(|| {
+            if dependency.license_file.is_some() {
+                "Custom License File".to_owned()
+            } else {
+                "N/A".to_owned()
             }
+        });
+        table.entry(license).or_default().push(dependency);
+    }
+    table
+}
+
+pub fn to_json_grouped_string(dependencies: Vec<DependencyDetails>) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&group_by_license(
+        dependencies,
+    ))?)
+}
+
+pub fn write_json_grouped(dependencies: Vec<DependencyDetails>) -> Result<()> {
+    write_json_to(buffered_stdout(), &group_by_license(dependencies))
+}
+
+fn write_json_to(mut writer: impl io::Write, value: &impl serde::Serialize) -> Result<()> {
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    writeln!(writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn to_json_string(dependencies: &[DependencyDetails]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&dependencies)?)
+}
+
+/// Renders the JSON Schema of the `--json` output, an array of
+/// [`DependencyDetails`].
+pub fn json_schema_string() -> Result<String> {
+    Ok(serde_json::to_string_pretty(&schemars::schema_for!(
+        Vec<DependencyDetails>
+    ))?)
+}
+
+/// Writes the dependencies as a JSON array as they are yielded, without
+/// collecting them first.
+pub fn write_json<'a>(dependencies: impl IntoIterator<Item = &'a DependencyDetails>) -> Result<()> {
+    let mut stdout = buffered_stdout();
+    let mut serializer = serde_json::Serializer::pretty(&mut stdout);
+    serde::Serializer::collect_seq(&mut serializer, dependencies)?;
+    writeln!(stdout)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// The data a `--template` is rendered with.
+#[derive(Debug, Serialize)]
+struct TemplateContext<'a> {
+    dependencies: &'a [DependencyDetails],
+    /// The dependencies grouped by license, as in [`group_by_license`].
+    licenses: BTreeMap<String, Vec<DependencyDetails>>,
+}
+
+/// Renders the dependencies through a handlebars `template`, which gets a
+/// `dependencies` array and a `licenses` object mapping every license to
+/// its crates.
+pub fn to_template_string(template: &str, dependencies: &[DependencyDetails]) -> Result<String> {
+    let context = TemplateContext {
+        dependencies,
+        licenses: group_by_license(dependencies.to_vec()),
+    };
+    Ok(handlebars::Handlebars::new().render_template(template, &context)?)
+}
+
+pub fn write_template(template: &str, dependencies: &[DependencyDetails]) -> Result<()> {
+    print!("{}", to_template_string(template, dependencies)?);
+    Ok(())
+}
+
+/// The `--json-envelope` output, keeping the warnings next to the
+/// dependencies they were found in, along with when and by which version of
+/// cargo-license the report was produced.
+#[derive(Debug, Serialize)]
+pub struct JsonEnvelope<'a> {
+    /// UTC time in RFC 3339 format, taken from `SOURCE_DATE_EPOCH` when set
+    /// so that reports can be reproduced.
+    pub generated_at: String,
+    pub tool_version: &'static str,
+    pub warnings: &'a [Warning],
+    pub dependencies: &'a [DependencyDetails],
+}
+
+impl<'a> JsonEnvelope<'a> {
+    #[must_use]
+    pub fn new(dependencies: &'a [DependencyDetails], warnings: &'a [Warning]) -> Self {
+        let seconds = std::env::var("SOURCE_DATE_EPOCH")
+            .ok()
+            .and_then(|epoch| epoch.parse().ok())
+            .unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |elapsed| elapsed.as_secs())
+            });
+        Self {
+            generated_at: format_timestamp(seconds),
+            tool_version: env!("CARGO_PKG_VERSION"),
+            warnings,
+            dependencies,
         }
-        connected
+    }
+}
+
+/// Checks that `date` is a `YYYY-MM-DD` calendar date, which then compares
+/// correctly with the RFC 3339 timestamps of the registry index as a string.
+#[must_use]
+pub fn is_valid_date(date: &str) -> bool {
+    let number = |s: &str, len: usize| {
+        (s.len() == len && s.bytes().all(|b| b.is_ascii_digit()))
+            .then(|| s.parse::<u32>().ok())
+            .flatten()
     };
+    let mut parts = date.split('-');
+    let (Some(year), Some(month), Some(day), None) = (
+        parts.next().and_then(|s| number(s, 4)),
+        parts.next().and_then(|s| number(s, 2)),
+        parts.next().and_then(|s| number(s, 2)),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&day)
+}
 
-    let mut detailed_dependencies = metadata
-        .packages
-        .iter()
-        .filter(|p| connected.contains(&p.id))
-        .filter(|p| node_name_filter.is_empty() || node_name_filter.contains(&p.name))
-        .filter(|p| !proc_macro_exclusions.contains(&p.name))
-        .map(DependencyDetails::new)
-        .collect::<Vec<_>>();
-    detailed_dependencies.sort_unstable();
-    Ok(detailed_dependencies)
+/// Formats seconds since the Unix epoch as an RFC 3339 UTC timestamp.
+fn format_timestamp(seconds: u64) -> String {
+    // Converts days since the epoch to a civil date, see
+    // <https://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+    let days = seconds / 86400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    let time = seconds % 86400;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
 }
 
-pub fn write_tsv(dependencies: &[DependencyDetails]) -> Result<()> {
-    let mut wtr = csv::WriterBuilder::new()
-        .delimiter(b'\t')
-        .quote_style(csv::QuoteStyle::Necessary)
-        .from_writer(io::stdout());
-    for dependency in dependencies {
-        wtr.serialize(dependency)?;
-    }
-    wtr.flush()?;
-    Ok(())
+pub fn to_json_envelope_string(
+    dependencies: &[DependencyDetails],
+    warnings: &[Warning],
+) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&JsonEnvelope::new(
+        dependencies,
+        warnings,
+    ))?)
+}
+
+pub fn write_json_envelope(dependencies: &[DependencyDetails], warnings: &[Warning]) -> Result<()> {
+    write_json_to(
+        buffered_stdout(),
+        &JsonEnvelope::new(dependencies, warnings),
+    )
+}
+
+pub fn to_policy_json_string(violations: &[PolicyViolation]) -> Result<String> {
+    Ok(serde_json::to_string_pretty(&PolicyReport::new(
+        violations,
+    ))?)
 }
 
-pub fn write_json(dependencies: &[DependencyDetails]) -> Result<()> {
-    println!("{}", serde_json::to_string_pretty(&dependencies)?);
+pub fn write_policy_json(violations: &[PolicyViolation]) -> Result<()> {
+    println!("{}", to_policy_json_string(violations)?);
     Ok(())
 }
 
-pub fn write_gitlab(dependencies: &[DependencyDetails]) -> Result<()> {
+pub fn to_gitlab_string(dependencies: &[DependencyDetails]) -> Result<String> {
     let dependencies = GitlabLicenseScanningReport::try_from(dependencies)?;
-    println!("{}", serde_json::to_string_pretty(&dependencies)?);
+    Ok(serde_json::to_string_pretty(&dependencies)?)
+}
+
+pub fn write_gitlab(dependencies: &[DependencyDetails]) -> Result<()> {
+    println!("{}", to_gitlab_string(dependencies)?);
+    Ok(())
+}
+
+/// Renders the dependencies as an [ORT](https://oss-review-toolkit.org)
+/// result containing only `analyzer.result.packages`.
+///
+/// Every package carries its identifier (`Crate::name:version`), declared
+/// license, description, repository as VCS and, for crates.io packages, the
+/// download URL as source artifact. Projects, dependency graphs, scopes and
+/// issues are not included.
+pub fn to_ort_string(dependencies: &[DependencyDetails]) -> Result<String> {
+    #[derive(Serialize)]
+    struct OrtResult {
+        analyzer: OrtAnalyzerRun,
+    }
+
+    #[derive(Serialize)]
+    struct OrtAnalyzerRun {
+        result: OrtAnalyzerResult,
+    }
+
+    #[derive(Serialize)]
+    struct OrtAnalyzerResult {
+        packages: Vec<OrtPackage>,
+    }
+
+    let result = OrtResult {
+        analyzer: OrtAnalyzerRun {
+            result: OrtAnalyzerResult {
+                packages: dependencies.iter().map(OrtPackage::from).collect(),
+            },
+        },
+    };
+    Ok(serde_json::to_string_pretty(&result)?)
+}
+
+/// Writes the dependencies as an ORT result, see [`to_ort_string`].
+pub fn write_ort(dependencies: &[DependencyDetails]) -> Result<()> {
+    println!("{}", to_ort_string(dependencies)?);
+    Ok(())
+}
+
+/// A package of a Syft JSON document.
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct SyftArtifact {
+    id: String,
+    name: String,
+    version: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    found_by: &'static str,
+    locations: Vec<()>,
+    licenses: Vec<SyftLicense>,
+    language: &'static str,
+    cpes: Vec<()>,
+    purl: String,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct SyftLicense {
+    value: String,
+    spdx_expression: String,
+    #[serde(rename = "type")]
+    kind: &'static str,
+    urls: Vec<()>,
+    locations: Vec<()>,
+}
+
+impl From<&DependencyDetails> for SyftArtifact {
+    fn from(dependency: &DependencyDetails) -> Self {
+        let purl = format!("pkg:cargo/{}@{}", dependency.name, dependency.version);
+        Self {
+            id: purl.clone(),
+            name: dependency.name.clone(),
+            version: dependency.version.to_string(),
+            kind: "rust-crate",
+            found_by: "cargo-license",
+            locations: Vec::new(),
+            licenses: dependency
+                .license
+                .iter()
+                .map(|license| SyftLicense {
+                    value: license.clone(),
+                    spdx_expression: license.clone(),
+                    kind: "declared",
+                    urls: Vec::new(),
+                    locations: Vec::new(),
+                })
+                .collect(),
+            language: "rust",
+            cpes: Vec::new(),
+            purl,
+        }
+    }
+}
+
+/// Renders the dependencies as a [Syft](https://github.com/anchore/syft)
+/// JSON document, which Grype can scan.
+///
+/// Only `artifacts`, `descriptor` and `schema` are populated. Every artifact
+/// carries its name, version, `rust-crate` type, `pkg:cargo` package URL
+/// (also used as its id) and declared license; crates with only a license
+/// file have no licenses. Locations, CPEs, relationships, the source and
+/// the distro are left out.
+pub fn to_syft_string(dependencies: &[DependencyDetails]) -> Result<String> {
+    #[derive(Serialize)]
+    struct SyftDocument {
+        artifacts: Vec<SyftArtifact>,
+        descriptor: SyftDescriptor,
+        schema: SyftSchema,
+    }
 
+    #[derive(Serialize)]
+    struct SyftDescriptor {
+        name: &'static str,
+        version: &'static str,
+    }
+
+    #[derive(Serialize)]
+    struct SyftSchema {
+        version: &'static str,
+        url: &'static str,
+    }
+
+    let document = SyftDocument {
+        artifacts: dependencies.iter().map(SyftArtifact::from).collect(),
+        descriptor: SyftDescriptor {
+            name: "cargo-license",
+            version: env!("CARGO_PKG_VERSION"),
+        },
+        schema: SyftSchema {
+            version: "16.0.0",
+            url:
+                "https://raw.githubusercontent.com/anchore/syft/main/schema/json/schema-16.0.0.json",
+        },
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// Writes the dependencies as a Syft JSON document, see [`to_syft_string`].
+pub fn write_syft(dependencies: &[DependencyDetails]) -> Result<()> {
+    println!("{}", to_syft_string(dependencies)?);
     Ok(())
 }
 
@@ -276,15 +3810,1144 @@ mod test {
     use super::*;
 
     #[test]
-    fn test_detailed() {
-        let cmd = MetadataCommand::new();
-        let detailed_dependencies =
-            get_dependencies_from_cargo_lock(cmd, GetDependenciesOpt::default()).unwrap();
-        assert!(!detailed_dependencies.is_empty());
-        for detailed_dependency in detailed_dependencies.iter() {
-            assert!(
-                detailed_dependency.license.is_some() || detailed_dependency.license_file.is_some()
+    fn test_normalize() {
+        for (input, expected) in [
+            ("MIT", "MIT"),
+            ("MIT/Apache-2.0", "Apache-2.0 OR MIT"),
+            ("MIT OR Apache-2.0", "Apache-2.0 OR MIT"),
+            ("Apache-2.0 / MIT / MIT", "Apache-2.0 OR MIT"),
+            ("MIT OR MIT", "MIT"),
+            ("Apache-2.0 AND Apache-2.0", "Apache-2.0"),
+            ("MIT AND Zlib AND MIT", "MIT AND Zlib"),
+            ("MIT AND Zlib OR MIT AND Zlib AND Zlib", "MIT AND Zlib"),
+            ("MIT OR (MIT AND Apache-2.0)", "MIT"),
+            ("MIT AND Apache-2.0 OR MIT", "MIT"),
+            ("MIT AND Zlib OR Zlib AND MIT", "MIT AND Zlib"),
+            (
+                "Zlib OR (MIT AND Apache-2.0) OR MIT AND Apache-2.0 AND Zlib",
+                "(MIT AND Apache-2.0) OR Zlib",
+            ),
+            ("(MIT OR Apache-2.0) AND MIT", "(MIT OR Apache-2.0) AND MIT"),
+            (
+                "(MIT AND Apache-2.0) AND (MIT AND Zlib)",
+                "(MIT AND Apache-2.0) AND (MIT AND Zlib)",
+            ),
+            (
+                "Borceux OR MIT AND BitTorrent-1.1",
+                "Borceux OR MIT AND BitTorrent-1.1",
+            ),
+        ] {
+            assert_eq!(normalize(input), expected, "normalizing `{input}`");
+        }
+    }
+
+    #[test]
+    fn test_normalize_unsorted() {
+        for (input, expected) in [
+            ("MIT/Apache-2.0", "MIT OR Apache-2.0"),
+            ("MIT OR Apache-2.0 OR MIT", "MIT OR Apache-2.0"),
+            ("Zlib OR MIT AND Zlib AND MIT", "Zlib"),
+            (
+                "Zlib OR (MIT AND Apache-2.0) OR MIT AND Apache-2.0 AND Zlib",
+                "Zlib OR (MIT AND Apache-2.0)",
+            ),
+            ("Unlicense OR MIT AND Zlib", "Unlicense OR MIT AND Zlib"),
+        ] {
+            assert_eq!(
+                normalize_with(input, false),
+                expected,
+                "normalizing `{input}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_precedence() {
+        for (input, expected) in [
+            ("MIT", false),
+            ("MIT OR Apache-2.0", false),
+            ("MIT AND Apache-2.0", false),
+            ("Borceux OR MIT AND BitTorrent-1.1", true),
+            ("MIT/Apache-2.0 AND BSD-3-Clause", true),
+            ("Borceux OR (MIT AND BitTorrent-1.1)", false),
+            ("(Borceux OR MIT) AND BitTorrent-1.1", false),
+            ("(MIT OR Apache-2.0 AND Zlib)", true),
+        ] {
+            assert_eq!(
+                has_ambiguous_precedence(input),
+                expected,
+                "checking `{input}`"
+            );
+        }
+    }
+
+    fn dependency(name: &str, license: Option<&str>) -> DependencyDetails {
+        DependencyDetails {
+            name: name.to_owned(),
+            version: Version::new(1, 0, 0),
+            authors: None,
+            repository: None,
+            license: license.map(normalize),
+            license_file: None,
+            description: None,
+            yanked: None,
+            dependents: None,
+            is_direct: None,
+            kinds: None,
+            dependency_path: None,
+            override_reason: None,
+            override_approved_by: None,
+            tags: None,
+            license_file_sha256: None,
+            features: None,
+            edition: None,
+            rust_version: None,
+            manifest_path: Utf8PathBuf::new(),
+            license_file_path: None,
+            crates_io: false,
+        }
+    }
+
+    #[test]
+    fn test_license_category() {
+        for (input, expected) in [
+            ("MIT", LicenseCategory::Permissive),
+            ("Apache-2.0 OR MIT", LicenseCategory::Permissive),
+            ("MPL-2.0", LicenseCategory::WeakCopyleft),
+            ("LGPL-2.1-or-later", LicenseCategory::WeakCopyleft),
+            ("GPL-3.0-only", LicenseCategory::StrongCopyleft),
+            ("GPL-3.0-only OR MIT", LicenseCategory::Permissive),
+            ("GPL-3.0-only AND MIT", LicenseCategory::StrongCopyleft),
+            (
+                "(MIT OR GPL-2.0) AND MPL-2.0",
+                LicenseCategory::WeakCopyleft,
+            ),
+            ("LicenseRef-Proprietary", LicenseCategory::Unknown),
+            ("Custom License File", LicenseCategory::Unknown),
+        ] {
+            assert_eq!(
+                LicenseCategory::of_expression(input),
+                expected,
+                "categorizing `{input}`"
+            );
+        }
+        assert_eq!(
+            LicenseCategory::of_expression_with("GPL-3.0-only OR MIT", Interpretation::WorstCase),
+            LicenseCategory::StrongCopyleft
+        );
+    }
+
+    #[test]
+    fn test_count_by_category() {
+        let dependencies = [
+            dependency("a", Some("MIT")),
+            dependency("b", Some("MIT OR GPL-3.0-only")),
+            dependency("c", None),
+            dependency("d", Some("MPL-2.0")),
+        ];
+        let counts = count_by_category(&dependencies, Interpretation::BestCase);
+        assert_eq!(
+            counts.into_iter().collect::<Vec<_>>(),
+            [
+                (LicenseCategory::Permissive, 2),
+                (LicenseCategory::WeakCopyleft, 1),
+                (LicenseCategory::StrongCopyleft, 0),
+                (LicenseCategory::Unknown, 1),
+            ]
+        );
+        let counts = count_by_category(&dependencies, Interpretation::WorstCase);
+        assert_eq!(counts[&LicenseCategory::StrongCopyleft], 1);
+        assert_eq!(
+            serde_json::to_string(&counts).unwrap(),
+            r#"{"Permissive":1,"WeakCopyleft":1,"StrongCopyleft":1,"Unknown":1}"#
+        );
+    }
+
+    #[test]
+    fn test_offers_license_choice() {
+        assert!(offers_license_choice("MIT OR Apache-2.0"));
+        assert!(offers_license_choice(
+            "(MIT OR Apache-2.0) AND Unicode-DFS-2016"
+        ));
+        assert!(!offers_license_choice("MIT"));
+        assert!(!offers_license_choice("MIT AND Apache-2.0"));
+    }
+
+    #[test]
+    fn test_diff_dependencies() {
+        let mut bumped = dependency("bumped", Some("MIT"));
+        bumped.version = Version::new(2, 0, 0);
+        let old = [
+            dependency("same", Some("MIT")),
+            dependency("relicensed", Some("MIT")),
+            dependency("bumped", Some("MIT")),
+            dependency("removed", Some("MIT")),
+        ];
+        let new = [
+            dependency("same", Some("MIT")),
+            dependency("relicensed", Some("Apache-2.0")),
+            bumped,
+            dependency("added", Some("MIT")),
+        ];
+        let diff = diff_dependencies(&old, &new);
+        let names =
+            |list: &[DependencyDetails]| list.iter().map(|d| d.name.clone()).collect::<Vec<_>>();
+        assert_eq!(names(&diff.added), ["added"]);
+        assert_eq!(names(&diff.removed), ["removed"]);
+        let changed = diff
+            .changed
+            .iter()
+            .map(|(old, new)| (old.name.as_str(), new.license.as_deref()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            changed,
+            [("bumped", Some("MIT")), ("relicensed", Some("Apache-2.0"))]
+        );
+        assert!(diff_dependencies(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_gitlab_license_exception() {
+        let dependency = dependency("llvm", Some("Apache-2.0 WITH LLVM-exception OR MIT"));
+        let mut licenses = GitlabLicense::parse_licenses(&dependency)
+            .unwrap()
+            .into_iter()
+            .map(|license| (license.id, license.exception))
+            .collect::<Vec<_>>();
+        licenses.sort_unstable();
+        assert_eq!(
+            licenses,
+            [("Apache-2.0", Some("LLVM-exception")), ("MIT", None)]
+        );
+    }
+
+    #[test]
+    fn test_license_hash() {
+        let deps = [dependency("a", Some("MIT")), dependency("b", Some("Zlib"))];
+        let reversed = [dependency("b", Some("Zlib")), dependency("a", Some("MIT"))];
+        let relicensed = [dependency("a", Some("MIT")), dependency("b", Some("MIT"))];
+        assert_eq!(license_hash(&deps), license_hash(&reversed));
+        assert_ne!(license_hash(&deps), license_hash(&relicensed));
+        assert_eq!(license_hash(&deps).len(), 64);
+    }
+
+    #[test]
+    fn test_license_overrides() {
+        let overrides: LicenseOverrides = toml::from_str(
+            r#"
+            openssl = "Apache-2.0"
+            ring = { license = "ISC", reason = "see LICENSE", approved_by = "legal" }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(overrides.license("openssl"), Some("Apache-2.0"));
+        assert_eq!(overrides.get("openssl").unwrap().reason, None);
+        let ring = overrides.get("ring").unwrap();
+        assert_eq!(ring.license, "ISC");
+        assert_eq!(ring.reason.as_deref(), Some("see LICENSE"));
+        assert_eq!(ring.approved_by.as_deref(), Some("legal"));
+    }
+
+    #[test]
+    fn test_crate_tags() {
+        let tags: CrateTags = toml::from_str(
+            r#"
+            serde = ["reviewed-2024", "core"]
+            ring = []
+            "#,
+        )
+        .unwrap();
+        assert_eq!(tags.get("serde").unwrap(), ["reviewed-2024", "core"]);
+        assert!(tags.get("ring").unwrap().is_empty());
+        assert_eq!(tags.get("openssl"), None);
+
+        let mut dependencies = vec![dependency("serde", Some("MIT")), dependency("ring", None)];
+        dependencies[0].tags = tags.get("serde").map(<[String]>::to_vec);
+        let tsv = to_tsv_string(&dependencies).unwrap();
+        assert!(tsv.lines().next().unwrap().ends_with("\ttags"));
+        assert!(tsv.contains("reviewed-2024,core"));
+    }
+
+    #[test]
+    fn test_ort_package() {
+        let mut dependency = dependency("serde", Some("Apache-2.0 OR MIT"));
+        dependency.crates_io = true;
+        let package = OrtPackage::from(&dependency);
+        assert_eq!(package.id, "Crate::serde:1.0.0");
+        assert_eq!(package.declared_licenses, ["Apache-2.0 OR MIT"]);
+        assert_eq!(
+            package.source_artifact.url,
+            "https://crates.io/api/v1/crates/serde/1.0.0/download"
+        );
+        assert_eq!(package.vcs.kind, "");
+    }
+
+    #[test]
+    fn test_minimal_license_set() {
+        let set = MinimalLicenseSet::of(&[
+            dependency("a", Some("MIT OR Apache-2.0")),
+            dependency("b", Some("Apache-2.0 OR BSL-1.0")),
+            dependency("c", Some("MIT OR Unlicense")),
+            dependency("d", Some("(MIT OR Apache-2.0) AND Unicode-DFS-2016")),
+            dependency("e", None),
+            dependency("f", Some("not a license")),
+        ]);
+        assert_eq!(set.licenses, ["Apache-2.0", "MIT", "Unicode-DFS-2016"]);
+        assert_eq!(set.crates, 4);
+        assert_eq!(set.skipped, 2);
+    }
+
+    #[test]
+    fn test_verdict() {
+        let passes = |project: &str, license: &str| {
+            verdict(&[dependency("a", Some(license))], project)
+                .unwrap()
+                .passed
+        };
+        assert!(passes("MIT", "MIT"));
+        assert!(passes("MIT", "Apache-2.0 OR MIT"));
+        assert!(passes("Apache-2.0", "BSD-3-Clause AND Zlib"));
+        assert!(passes("MIT OR Apache-2.0", "MPL-2.0"));
+        assert!(passes("GPL-3.0-only", "Apache-2.0"));
+        assert!(passes("GPL-3.0-only", "GPL-2.0-or-later"));
+        assert!(passes("GPL-2.0-or-later", "LGPL-2.1-or-later"));
+        assert!(passes("AGPL-3.0-only", "GPL-3.0-only"));
+        assert!(!passes("MIT", "GPL-3.0-only"));
+        assert!(!passes("MIT", "LGPL-2.1-only"));
+        assert!(!passes("GPL-2.0-only", "Apache-2.0"));
+        assert!(!passes("GPL-3.0-only", "GPL-2.0-only"));
+        assert!(!passes("MIT", "not a license"));
+
+        let dependencies = [dependency("a", Some("MIT")), dependency("b", None)];
+        let result = verdict(&dependencies, "MIT").unwrap();
+        assert!(!result.passed);
+        assert_eq!(result.incompatible[0].name, "b");
+        assert!(verdict(&[], "not a license").is_err());
+    }
+
+    #[test]
+    fn test_syft() {
+        let json = to_syft_string(&[
+            dependency("serde", Some("Apache-2.0 OR MIT")),
+            dependency("custom", None),
+        ])
+        .unwrap();
+        let document: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let serde = &document["artifacts"][0];
+        assert_eq!(serde["name"], "serde");
+        assert_eq!(serde["type"], "rust-crate");
+        assert_eq!(serde["purl"], "pkg:cargo/serde@1.0.0");
+        assert_eq!(serde["licenses"][0]["spdxExpression"], "Apache-2.0 OR MIT");
+        assert_eq!(document["artifacts"][1]["licenses"], serde_json::json!([]));
+        assert_eq!(document["descriptor"]["name"], "cargo-license");
+    }
+
+    #[test]
+    fn test_incompatible_licenses() {
+        let pair = |a: &str, b: &str| vec![(a.to_owned(), b.to_owned())];
+        assert_eq!(
+            incompatible_licenses("GPL-2.0-only AND Apache-2.0"),
+            pair("GPL-2.0", "Apache-2.0")
+        );
+        assert_eq!(
+            incompatible_licenses("(GPL-2.0-only OR GPL-3.0-only) AND OpenSSL"),
+            [
+                ("GPL-2.0".to_owned(), "OpenSSL".to_owned()),
+                ("GPL-3.0".to_owned(), "OpenSSL".to_owned()),
+            ]
+        );
+        assert!(incompatible_licenses("GPL-2.0-or-later AND Apache-2.0").is_empty());
+        assert!(incompatible_licenses("GPL-3.0-only AND Apache-2.0").is_empty());
+        assert!(incompatible_licenses("(GPL-2.0-only OR MIT) AND Apache-2.0").is_empty());
+        assert!(incompatible_licenses("GPL-2.0-only OR Apache-2.0").is_empty());
+    }
+
+    #[test]
+    fn test_to_tsv_string() {
+        let mut overridden = dependency("b", Some("MIT"));
+        overridden.override_reason = Some("checked".to_owned());
+        let tsv = to_tsv_string(&[dependency("a", Some("Zlib")), overridden]).unwrap();
+        let rows = tsv
+            .lines()
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].last(), Some(&"override_reason"));
+        assert_eq!(rows[1].last(), Some(&""));
+        assert_eq!(rows[2].last(), Some(&"checked"));
+
+        let mut featured = dependency("b", Some("MIT"));
+        featured.features = Some(vec!["default".to_owned(), "std".to_owned()]);
+        let tsv = to_tsv_string(&[dependency("a", Some("Zlib")), featured]).unwrap();
+        let rows = tsv
+            .lines()
+            .map(|line| line.split('\t').collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(rows[0].last(), Some(&"features"));
+        assert_eq!(rows[1].last(), Some(&""));
+        assert_eq!(rows[2].last(), Some(&"default,std"));
+    }
+
+    #[test]
+    fn test_spdx_stats() {
+        let mut license_file = dependency("c", None);
+        license_file.license_file = Some("LICENSE".to_owned());
+        let stats = SpdxStats::of(&[
+            dependency("a", Some("MIT OR Apache-2.0")),
+            dependency("b", Some("Custom license")),
+            license_file,
+            dependency("d", None),
+            dependency("e", Some("MIT")),
+        ]);
+        assert_eq!(
+            stats,
+            SpdxStats {
+                valid: 2,
+                license_file_only: 1,
+                invalid: 1,
+                missing: 1,
+            }
+        );
+    }
+
+    #[test]
+    fn test_dedupe_dependencies() {
+        let mut described = dependency("a", Some("MIT"));
+        described.description = Some("A crate".to_owned());
+        let mut other_path = dependency("b", Some("MIT"));
+        other_path.manifest_path = "b/Cargo.toml".into();
+        let mut dependencies = vec![
+            dependency("a", Some("MIT")),
+            described,
+            dependency("b", Some("MIT")),
+            other_path,
+            dependency("c", Some("MIT")),
+        ];
+        dependencies.sort_unstable();
+        let warnings = dedupe_dependencies(&mut dependencies);
+        assert_eq!(
+            dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "b", "c"]
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].kind, WarningKind::DuplicateCrate);
+
+        // Directness and dependency kinds are merged instead of warned about.
+        let mut direct = dependency("a", Some("MIT"));
+        direct.is_direct = Some(true);
+        direct.kinds = Some(vec!["normal".to_owned()]);
+        let mut transitive = dependency("a", Some("MIT"));
+        transitive.is_direct = Some(false);
+        transitive.kinds = Some(vec!["build".to_owned()]);
+        let mut dependencies = vec![transitive, direct];
+        assert!(dedupe_dependencies(&mut dependencies).is_empty());
+        assert_eq!(dependencies.len(), 1);
+        assert_eq!(dependencies[0].is_direct, Some(true));
+        assert_eq!(
+            dependencies[0].kinds.as_deref().unwrap(),
+            ["build", "normal"]
+        );
+
+        // Duplicates are dropped wherever they are, keeping the order.
+        let mut dependencies = vec![
+            dependency("root", Some("MIT")),
+            dependency("a", Some("MIT")),
+            dependency("root2", Some("MIT")),
+            dependency("a", Some("MIT")),
+        ];
+        assert!(dedupe_dependencies(&mut dependencies).is_empty());
+        assert_eq!(
+            dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>(),
+            ["root", "a", "root2"]
+        );
+    }
+
+    #[test]
+    fn test_json_envelope() {
+        let warnings = [Warning::new(WarningKind::MissingLicense, "no license")];
+        let json = to_json_envelope_string(&[dependency("a", Some("MIT"))], &warnings).unwrap();
+        let envelope: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(envelope["warnings"][0]["kind"], "missing-license");
+        assert_eq!(envelope["warnings"][0]["message"], "no license");
+        assert_eq!(envelope["dependencies"][0]["name"], "a");
+        assert_eq!(envelope["tool_version"], env!("CARGO_PKG_VERSION"));
+        assert!(envelope["generated_at"].as_str().unwrap().ends_with('Z'));
+
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14T22:13:20Z");
+    }
+
+    #[test]
+    fn test_is_valid_date() {
+        for date in ["2024-01-31", "2024-02-29", "2000-02-29", "2023-12-01"] {
+            assert!(is_valid_date(date), "`{date}` is valid");
+        }
+        for date in [
+            "2024-13-45",
+            "2024-00-10",
+            "2024-04-31",
+            "2023-02-29",
+            "1900-02-29",
+            "2024-01-00",
+            "2024-1-01",
+            "24-01-01",
+            "2024-01-01-01",
+            "2024/01/01",
+            "+024-01-01",
+        ] {
+            assert!(!is_valid_date(date), "`{date}` is invalid");
+        }
+    }
+
+    #[test]
+    fn test_template() {
+        let template = "{{#each licenses}}{{@key}}:{{#each this}} {{name}}{{/each}}\n{{/each}}\
+                        {{#each dependencies}}{{name}} {{version}}\n{{/each}}";
+        let rendered = to_template_string(
+            template,
+            &[
+                dependency("a", Some("MIT")),
+                dependency("b", Some("Zlib")),
+                dependency("c", Some("MIT")),
+            ],
+        )
+        .unwrap();
+        assert_eq!(rendered, "MIT: a c\nZlib: b\na 1.0.0\nb 1.0.0\nc 1.0.0\n");
+        assert!(to_template_string("{{#each}}", &[]).is_err());
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value =
+            serde_json::from_str(&json_schema_string().unwrap()).unwrap();
+        let properties = &schema["definitions"]["DependencyDetails"]["properties"];
+        assert!(properties["license"].is_object());
+        assert!(properties["manifest_path"].is_null());
+    }
+
+    #[test]
+    fn test_normalize_authors() {
+        for (input, expected) in [
+            ("Foo <foo@example.com>", "Foo <foo@example.com>"),
+            ("  Foo   <foo@example.com> ", "Foo <foo@example.com>"),
+            (
+                "foo@example.com|Foo <FOO@example.com>",
+                "Foo <FOO@example.com>",
+            ),
+            (
+                "<foo@example.com>|The Foo Team",
+                "foo@example.com|The Foo Team",
+            ),
+            ("The Foo Team|the foo team|", "The Foo Team"),
+            (
+                "Foo <foo@example.com>|Bar <foo@example.com>",
+                "Foo <foo@example.com>",
+            ),
+        ] {
+            assert_eq!(
+                normalize_authors(input, "|"),
+                expected,
+                "normalizing `{input}`"
+            );
+        }
+        assert_eq!(
+            normalize_authors("foo@example.com; Foo <foo@example.com>; Bar", "; "),
+            "Foo <foo@example.com>; Bar"
+        );
+    }
+
+    #[test]
+    fn test_normalize_repository() {
+        for input in [
+            "https://github.com/foo/bar",
+            "https://github.com/foo/bar.git",
+            "https://github.com/foo/bar/",
+            "http://github.com/foo/bar",
+            "git@github.com:foo/bar.git",
+            "ssh://git@github.com/foo/bar.git",
+            "git://github.com/foo/bar",
+            "git+https://github.com/foo/bar.git",
+        ] {
+            assert_eq!(
+                normalize_repository(input),
+                "https://github.com/foo/bar",
+                "normalizing `{input}`"
             );
         }
+        assert_eq!(normalize_repository("not a url"), "not a url");
+    }
+
+    #[test]
+    fn test_author_counts() {
+        let mut a = dependency("a", None);
+        a.authors = Some("foo@example.com|Bar|foo@example.com".to_owned());
+        let mut b = dependency("b", None);
+        b.authors = Some("Foo <FOO@example.com>".to_owned());
+        let mut c = dependency("c", None);
+        c.authors = Some("Baz|bar".to_owned());
+        assert_eq!(
+            author_counts(&[a, b, c, dependency("d", None)]),
+            [
+                AuthorCount {
+                    author: "Bar".to_owned(),
+                    crates: 2,
+                },
+                AuthorCount {
+                    author: "Foo <FOO@example.com>".to_owned(),
+                    crates: 2,
+                },
+                AuthorCount {
+                    author: "Baz".to_owned(),
+                    crates: 1,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_license_info() {
+        let info = LicenseInfo::lookup("0BSD").unwrap();
+        assert_eq!(info.full_name, "BSD Zero Clause License");
+        assert!(info.osi_approved);
+        assert!(!info.copyleft);
+        assert_eq!(info.url, "https://spdx.org/licenses/0BSD.html");
+        assert!(LicenseInfo::lookup("GPL-3.0").unwrap().deprecated);
+        assert!(LicenseInfo::lookup("not-a-license").is_none());
+
+        assert_eq!(LicenseInfo::lookup("Apache 2.0").unwrap().id, "Apache-2.0");
+        let info = LicenseInfo::lookup("GPL-3.0+").unwrap();
+        assert_eq!((info.id, info.or_later), ("GPL-3.0-or-later", false));
+        let info = LicenseInfo::lookup("Apache-2.0+").unwrap();
+        assert_eq!((info.id, info.or_later), ("Apache-2.0", true));
+        for id in ["MITX", "MIT-foo", "MIT++"] {
+            assert!(LicenseInfo::lookup(id).is_none(), "`{id}` is unknown");
+            assert_eq!(LicenseInfo::suggest(id), Some("MIT"));
+        }
+        assert_eq!(LicenseInfo::suggest("not-a-license"), None);
+    }
+
+    #[test]
+    fn test_license_obligations() {
+        use Obligation::{Attribution, PatentGrant, ReviewRequired, StateChanges};
+        let obligations = license_obligations(&[
+            dependency("dual", Some("MIT OR Apache-2.0")),
+            dependency("mit", Some("MIT")),
+            dependency("custom", Some("LicenseRef-Custom")),
+            dependency("none", None),
+        ]);
+        let summary = obligations
+            .iter()
+            .map(|o| (o.license.as_str(), o.obligations.clone(), o.crates.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (
+                    "Apache-2.0",
+                    vec![Attribution, StateChanges, PatentGrant],
+                    1
+                ),
+                ("LicenseRef-Custom", vec![ReviewRequired], 1),
+                ("MIT", vec![Attribution], 2),
+                ("N/A", vec![ReviewRequired], 1),
+            ]
+        );
+        assert_eq!(Obligation::of_license("GPL-2.0-or-later").len(), 4);
+    }
+
+    #[test]
+    fn test_parse_index_cache() {
+        let cache = b"\x03\x02\x00\x00\x00etag\x000.1.0\x00\
+            {\"name\":\"foo\",\"vers\":\"0.1.0\",\"yanked\":true}\x000.2.0\x00\
+            {\"name\":\"foo\",\"vers\":\"0.2.0\",\"yanked\":false,\
+            \"pubtime\":\"2024-05-01T10:00:00Z\"}\x00";
+        let entries = parse_index_cache(cache)
+            .into_iter()
+            .map(|entry| (entry.vers.to_string(), entry.yanked, entry.pubtime))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                ("0.1.0".to_owned(), true, None),
+                (
+                    "0.2.0".to_owned(),
+                    false,
+                    Some("2024-05-01T10:00:00Z".to_owned())
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn test_normalize_license_text() {
+        let (a, a_copyright) = normalize_license_text(
+            "MIT License\n\nCopyright (c) 2015 Foo\n\nPermission is hereby granted,\nfree of charge",
+        );
+        let (b, b_copyright) = normalize_license_text(
+            "MIT License\n\n  Copyright 2020 Bar  \n\nPermission is hereby granted, free of\ncharge\n",
+        );
+        assert_eq!(a, b);
+        assert_eq!(
+            a,
+            "MIT License\n\nPermission is hereby granted, free of charge"
+        );
+        assert_eq!(a_copyright, ["Copyright (c) 2015 Foo"]);
+        assert_eq!(b_copyright, ["Copyright 2020 Bar"]);
+    }
+
+    #[test]
+    fn test_check_osi_approved() {
+        let dependencies = [
+            dependency("a", Some("MIT")),
+            dependency("b", Some("MIT OR CC0-1.0")),
+            dependency("c", Some("CC0-1.0")),
+            dependency("d", Some("MIT AND CC0-1.0")),
+            dependency("e", Some("LicenseRef-Custom")),
+            dependency("f", None),
+        ];
+        let rules = |interpretation| {
+            check_osi_approved(&dependencies, interpretation)
+                .into_iter()
+                .map(|violation| (violation.name, violation.rule))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            rules(Interpretation::BestCase),
+            [
+                ("c".to_owned(), PolicyRule::NotOsiApproved),
+                ("d".to_owned(), PolicyRule::NotOsiApproved),
+                ("e".to_owned(), PolicyRule::NotOsiApproved),
+                ("f".to_owned(), PolicyRule::Unlicensed),
+            ]
+        );
+        assert_eq!(rules(Interpretation::WorstCase).len(), 5);
+
+        let fsf = check_fsf_libre(
+            &[
+                dependency("a", Some("GPL-3.0-only")),
+                dependency("b", Some("MIT-0")),
+                dependency("c", Some("MIT-0 OR Zlib")),
+            ],
+            Interpretation::BestCase,
+        );
+        assert_eq!(
+            fsf.iter()
+                .map(|v| (v.name.as_str(), v.rule))
+                .collect::<Vec<_>>(),
+            [("b", PolicyRule::NotFsfLibre)]
+        );
+    }
+
+    #[test]
+    fn test_check_deprecated() {
+        let violations = check_deprecated(&[
+            dependency("a", Some("GPL-3.0")),
+            dependency("b", Some("GPL-3.0-only")),
+            dependency("c", Some("MIT OR GPL-2.0+")),
+            dependency("d", None),
+        ]);
+        assert_eq!(
+            violations
+                .iter()
+                .map(|v| v.name.as_str())
+                .collect::<Vec<_>>(),
+            ["a", "c"]
+        );
+    }
+
+    #[test]
+    fn test_license_policy() {
+        let config: DenyConfig = toml::from_str(
+            r#"
+            [licenses]
+            allow = ["MIT", "Apache-2.0"]
+            deny = ["GPL-3.0"]
+            exceptions = [{ allow = ["Zlib"], crate = "adler32" }]
+            "#,
+        )
+        .unwrap();
+        let policy = config.licenses.unwrap();
+        let dependencies = [
+            dependency("dual", Some("MIT/GPL-3.0")),
+            dependency("adler32", Some("Zlib")),
+            dependency("zlib-user", Some("Zlib")),
+            dependency("gpl", Some("GPL-3.0")),
+            dependency("none", None),
+            dependency("broken", Some("not a license ((")),
+        ];
+        let violations = policy
+            .check(&dependencies)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.name, v.rule))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            [
+                ("zlib-user".to_owned(), PolicyRule::NotAllowed),
+                ("gpl".to_owned(), PolicyRule::Denied),
+                ("none".to_owned(), PolicyRule::Unlicensed),
+                ("broken".to_owned(), PolicyRule::InvalidExpression),
+            ]
+        );
+        let worst_case = policy
+            .check_with(&dependencies[..1], Interpretation::WorstCase)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.name, v.rule))
+            .collect::<Vec<_>>();
+        assert_eq!(worst_case, [("dual".to_owned(), PolicyRule::Denied)]);
+    }
+
+    #[test]
+    fn test_license_policy_scoped_allow() {
+        let config: DenyConfig = toml::from_str(
+            r#"
+            [licenses]
+            allow = [
+                "MIT",
+                { license = "GPL-3.0", crates = ["our-gpl-tool", "old-tool@<2"] },
+            ]
+            exceptions = [{ allow = ["Zlib"], crate = "adler32@1" }]
+            "#,
+        )
+        .unwrap();
+        let policy = config.licenses.unwrap();
+        let mut new_tool = dependency("old-tool", Some("GPL-3.0"));
+        new_tool.version = Version::new(2, 0, 0);
+        let mut new_adler32 = dependency("adler32", Some("Zlib"));
+        new_adler32.version = Version::new(2, 0, 0);
+        let dependencies = [
+            dependency("our-gpl-tool", Some("GPL-3.0")),
+            dependency("old-tool", Some("GPL-3.0")),
+            new_tool,
+            dependency("other", Some("GPL-3.0")),
+            dependency("adler32", Some("Zlib")),
+            new_adler32,
+        ];
+        let violations = policy
+            .check(&dependencies)
+            .unwrap()
+            .into_iter()
+            .map(|v| (v.name, v.version.major))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            violations,
+            [
+                ("old-tool".to_owned(), 2),
+                ("other".to_owned(), 1),
+                ("adler32".to_owned(), 2),
+            ]
+        );
+
+        let config: DenyConfig = toml::from_str(
+            r#"
+            [licenses]
+            allow = [{ license = "MIT", crates = ["a@not a version"] }]
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(
+            config.licenses.unwrap().check(&dependencies),
+            Err(CargoLicenseError::PolicyCrate { .. })
+        ));
+    }
+
+    /// Collects the dependencies of the workspace in `tests/fixtures`, whose
+    /// crates only depend on each other.
+    fn fixture(opt: GetDependenciesOpt) -> (Vec<DependencyDetails>, Vec<Warning>) {
+        fixture_at("workspace", opt)
+    }
+
+    fn fixture_at(name: &str, opt: GetDependenciesOpt) -> (Vec<DependencyDetails>, Vec<Warning>) {
+        let mut cmd = MetadataCommand::new();
+        cmd.current_dir(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("tests/fixtures")
+                .join(name),
+        );
+        get_dependencies_with_warnings(cmd, opt).unwrap()
+    }
+
+    fn licenses(dependencies: &[DependencyDetails]) -> Vec<(&str, Option<&str>)> {
+        dependencies
+            .iter()
+            .map(|d| (d.name.as_str(), d.license.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn test_detailed() {
+        let (dependencies, warnings) = fixture(GetDependenciesOpt::default());
+        assert_eq!(
+            licenses(&dependencies),
+            [
+                ("build-helper", Some("Zlib")),
+                ("custom", None),
+                ("dev-helper", Some("BSD-3-Clause")),
+                ("dual", Some("Apache-2.0 OR MIT")),
+                ("fixture", Some("MIT")),
+                ("unlicensed", None),
+            ]
+        );
+        let custom = &dependencies[1];
+        assert_eq!(custom.license_file.as_deref(), Some("LICENSE-CUSTOM"));
+        assert_eq!(custom.version, Version::new(0, 3, 0));
+        assert_eq!(
+            dependencies[3].authors.as_deref(),
+            Some("Dual Author <dual@example.com>|Second Author")
+        );
+        assert_eq!(
+            warnings,
+            [Warning::new(
+                WarningKind::MissingLicense,
+                "`unlicensed 0.2.1` has neither a license nor a license file"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_vendored_dir() {
+        let dir = std::env::temp_dir().join(format!("cargo-license-vendor-{}", std::process::id()));
+        for name in ["foo", "foo-0.1.0", "bar-1.0.0"] {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            std::fs::write(dir.join(name).join("Cargo.toml"), "").unwrap();
+        }
+        let vendor_dir = Utf8Path::from_path(&dir).unwrap();
+        let version = |v| Version::parse(v).unwrap();
+        let found = |name, v| vendored_dir(vendor_dir, name, &version(v));
+        assert_eq!(found("foo", "0.1.0"), Some(vendor_dir.join("foo-0.1.0")));
+        assert_eq!(found("foo", "0.2.0"), Some(vendor_dir.join("foo")));
+        assert_eq!(found("bar", "1.0.0"), Some(vendor_dir.join("bar-1.0.0")));
+        assert_eq!(found("bar", "2.0.0"), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_locked_never_writes_lockfile() {
+        fn copy_dir(from: &Path, to: &Path) {
+            std::fs::create_dir_all(to).unwrap();
+            for entry in std::fs::read_dir(from).unwrap() {
+                let entry = entry.unwrap();
+                let to = to.join(entry.file_name());
+                if entry.file_type().unwrap().is_dir() {
+                    copy_dir(&entry.path(), &to);
+                } else if entry.file_name() != "Cargo.lock" {
+                    std::fs::copy(entry.path(), to).unwrap();
+                }
+            }
+        }
+
+        let dir = std::env::temp_dir().join(format!("cargo-license-locked-{}", std::process::id()));
+        copy_dir(
+            Path::new(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/workspace"
+            )),
+            &dir,
+        );
+        let mut cmd = MetadataCommand::new();
+        cmd.current_dir(&dir)
+            .other_options(vec!["--locked".to_owned()]);
+        let result = get_dependencies_with_warnings(cmd, GetDependenciesOpt::default());
+        let lockfile_exists = dir.join("Cargo.lock").exists();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(result, Err(CargoLicenseError::LockfileUpdate(_))));
+        assert!(!lockfile_exists);
+    }
+
+    #[test]
+    fn test_exclude_workspace_members() {
+        let opt = GetDependenciesOpt::builder()
+            .exclude_workspace_members(["fixture", "dev-helper"])
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>(),
+            ["build-helper", "custom", "dual", "unlicensed"]
+        );
+
+        let mut cmd = MetadataCommand::new();
+        cmd.manifest_path(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/workspace/Cargo.toml"
+        ));
+        let opt = GetDependenciesOpt::builder()
+            .exclude_workspace_members(["missing"])
+            .build();
+        assert!(matches!(
+            get_dependencies_with_warnings(cmd, opt),
+            Err(CargoLicenseError::UnknownPackage(name)) if name == "missing"
+        ));
+    }
+
+    #[test]
+    fn test_author_separator() {
+        let opt = GetDependenciesOpt::builder()
+            .author_separator("; ")
+            .dedupe_authors(true)
+            .build();
+        let (dependencies, _) = fixture(opt);
+        let dual = dependencies.iter().find(|d| d.name == "dual").unwrap();
+        assert_eq!(
+            dual.authors.as_deref(),
+            Some("Dual Author <dual@example.com>; Second Author")
+        );
+    }
+
+    #[test]
+    fn test_detailed_dependency_kinds() {
+        let opt = GetDependenciesOpt::builder()
+            .avoid_dev_deps(true)
+            .avoid_build_deps(true)
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            licenses(&dependencies),
+            [
+                ("custom", None),
+                ("dual", Some("Apache-2.0 OR MIT")),
+                ("fixture", Some("MIT")),
+                ("unlicensed", None),
+            ]
+        );
+
+        let opt = GetDependenciesOpt::builder()
+            .include_transitive_count(true)
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            dependencies
+                .iter()
+                .filter(|d| d.is_direct == Some(true))
+                .count(),
+            5
+        );
+        assert_eq!(
+            dependencies
+                .iter()
+                .find(|d| d.name == "fixture")
+                .unwrap()
+                .is_direct,
+            Some(false)
+        );
+
+        let opt = GetDependenciesOpt::builder()
+            .include_dependency_paths(true)
+            .build();
+        let (dependencies, _) = fixture(opt);
+        let licensee = spdx::Licensee::parse("Zlib").unwrap();
+        let users = who_uses(&dependencies, &licensee);
+        assert_eq!(users.len(), 1);
+        assert_eq!(
+            users[0].dependency_path.as_deref().unwrap(),
+            ["fixture 0.1.0", "build-helper 2.0.0"]
+        );
+
+        let opt = GetDependenciesOpt::builder().include_kinds(true).build();
+        let (dependencies, _) = fixture(opt);
+        let kinds = |name: &str| {
+            dependencies
+                .iter()
+                .find(|d| d.name == name)
+                .and_then(|d| d.kinds.clone())
+                .unwrap()
+        };
+        assert_eq!(kinds("dual"), ["normal"]);
+        assert_eq!(kinds("build-helper"), ["build"]);
+        assert_eq!(kinds("dev-helper"), ["dev"]);
+
+        let opt = GetDependenciesOpt::builder()
+            .kinds([DependencyKind::Development])
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>(),
+            ["dev-helper"]
+        );
+
+        // `nested` depends on `runtime` and `shared` 2.0.0; `runtime` depends
+        // on `shared` 1.0.0 and has a build dependency on `codegen`.
+        let opt = GetDependenciesOpt::builder()
+            .build_deps_only(true)
+            .kinds([DependencyKind::Build])
+            .include_kinds(true)
+            .build();
+        let (dependencies, _) = fixture_at("nested", opt);
+        assert_eq!(licenses(&dependencies), [("codegen", Some("Zlib"))]);
+        assert_eq!(dependencies[0].kinds.as_deref().unwrap(), ["build"]);
+
+        let opt = GetDependenciesOpt::builder()
+            .include_transitive_count(true)
+            .build();
+        let (dependencies, _) = fixture_at("nested", opt);
+        let direct = dependencies
+            .iter()
+            .filter(|d| d.is_direct == Some(true))
+            .map(|d| format!("{} {}", d.name, d.version))
+            .collect::<Vec<_>>();
+        assert_eq!(direct, ["runtime 0.1.0", "shared 2.0.0"]);
+
+        let opt = GetDependenciesOpt::builder()
+            .sort_by(SortOrder::Topological)
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            dependencies
+                .iter()
+                .map(|d| d.name.as_str())
+                .collect::<Vec<_>>(),
+            [
+                "fixture",
+                "build-helper",
+                "custom",
+                "dev-helper",
+                "dual",
+                "unlicensed"
+            ]
+        );
+
+        let opt = GetDependenciesOpt::builder().build_deps_only(true).build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(licenses(&dependencies), [("build-helper", Some("Zlib"))]);
+    }
+
+    #[test]
+    fn test_detailed_license_match() {
+        let opt = GetDependenciesOpt::builder()
+            .include_license_match(["*MIT*"])
+            .exclude_license_match(["MIT"])
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            licenses(&dependencies),
+            [("dual", Some("Apache-2.0 OR MIT"))]
+        );
+
+        let opt = GetDependenciesOpt::builder()
+            .exclude_license(["MIT", "Zlib"])
+            .build();
+        let (dependencies, _) = fixture(opt);
+        assert_eq!(
+            licenses(&dependencies),
+            [
+                ("custom", None),
+                ("dev-helper", Some("BSD-3-Clause")),
+                ("unlicensed", None),
+            ]
+        );
     }
 }
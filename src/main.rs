@@ -1,52 +1,105 @@
 #![deny(clippy::all)]
 #![warn(clippy::pedantic)]
 
-use ansi_term::Colour::Green;
+use ansi_term::Colour::{Fixed, Green, Purple, Red, Yellow};
 use ansi_term::Style;
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use cargo_license::{
-    get_dependencies_from_cargo_lock, write_gitlab, write_json, write_tsv, DependencyDetails,
-    GetDependenciesOpt,
+    author_counts, bundle_license_texts, check_compatibility, check_deprecated, check_elections,
+    check_fsf_libre, check_osi_approved, configured_build_target, count_by_category,
+    dedupe_dependencies, diff_dependencies, get_dependencies_with_warnings, group_by_license,
+    is_valid_date, json_schema_string, license_hash, license_obligations, read_exclude_file,
+    read_json, verdict, who_uses, write_gitlab, write_json, write_json_envelope,
+    write_json_grouped, write_ort, write_policy_json, write_syft, write_template, write_toml,
+    write_tsv, CrateTags, DependencyDetails, GetDependenciesOpt, Interpretation, LicenseCategory,
+    LicenseFilePaths, LicenseInfo, LicenseOverrides, LicensePolicy, MinimalLicenseSet, Obligation,
+    PolicyViolation, ReportDiff, SortOrder, SpdxStats, Warning, WarningKind,
 };
-use cargo_metadata::{CargoOpt, MetadataCommand};
+use cargo_metadata::camino::Utf8PathBuf;
+use cargo_metadata::{CargoOpt, DependencyKind, MetadataCommand};
 use clap::builder::styling::AnsiColor;
 use clap::builder::Styles;
-use clap::{Parser, ValueEnum};
+use clap::{ArgGroup, Parser, ValueEnum};
+use serde_json::json;
 use std::borrow::Cow;
-use std::collections::btree_map::Entry::{Occupied, Vacant};
+use std::collections::hash_map::RandomState;
 use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
 use std::io::{self, IsTerminal};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::exit;
+use std::time::{Duration, Instant};
+
+fn category_style(category: LicenseCategory) -> Style {
+    match category {
+        LicenseCategory::Permissive => Green.bold(),
+        LicenseCategory::WeakCopyleft => Yellow.bold(),
+        LicenseCategory::StrongCopyleft => Red.bold(),
+        LicenseCategory::Unknown => Purple.bold(),
+    }
+}
+
+/// Collapses the licenses of fewer than `threshold` crates into a single
+/// trailing "Other" group. Only permissive licenses are collapsed, copyleft
+/// licenses, crates without a license and crates with only a license file
+/// always stand out.
+fn collapse_small_groups(
+    table: BTreeMap<String, Vec<DependencyDetails>>,
+    interpretation: Interpretation,
+    threshold: Option<usize>,
+) -> Vec<(String, Vec<DependencyDetails>)> {
+    let mut groups = Vec::new();
+    let mut other = Vec::new();
+    for (license, crates) in table {
+        let permissive = LicenseCategory::of_expression_with(&license, interpretation)
+            == LicenseCategory::Permissive;
+        if permissive && threshold.is_some_and(|threshold| crates.len() < threshold) {
+            other.extend(crates);
+        } else {
+            groups.push((license, crates));
+        }
+    }
+    if !other.is_empty() {
+        other.sort_unstable();
+        groups.push(("Other".to_owned(), other));
+    }
+    groups
+}
 
 fn group_by_license_type(
     dependencies: Vec<DependencyDetails>,
     display_authors: bool,
-    enable_color: bool,
+    display_stats: bool,
+    theme: Theme,
+    wrap: Option<usize>,
+    interpretation: Interpretation,
+    threshold: Option<usize>,
 ) {
-    let mut table: BTreeMap<String, Vec<DependencyDetails>> = BTreeMap::new();
+    let table = group_by_license(dependencies);
 
-    for dependency in dependencies {
-        let license_file = dependency.license_file.as_ref();
-        let license = dependency.license.clone().unwrap_or_else(move || {
-            if license_file.is_some() {
-                "Custom License File".to_owned()
+    if display_stats {
+        let crate_count = table.values().map(Vec::len).sum::<usize>();
+        println!(
+            "{} {} across {} distinct {}",
+            colored(&crate_count.to_string(), &Green.bold(), theme),
+            if crate_count == 1 { "crate" } else { "crates" },
+            colored(&table.len().to_string(), &Green.bold(), theme),
+            if table.len() == 1 {
+                "license"
             } else {
-                "N/A".to_owned()
-            }
-        });
-        match table.entry(license) {
-            Vacant(e) => {
-                e.insert(vec![dependency]);
-            }
-            Occupied(mut e) => {
-                e.get_mut().push(dependency);
-            }
-        };
+                "licenses"
+            },
+        );
     }
 
-    for (license, crates) in table {
+    for (license, crates) in collapse_small_groups(table, interpretation, threshold) {
+        let style = category_style(LicenseCategory::of_expression_with(
+            &license,
+            interpretation,
+        ));
         let crate_names = crates.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        let count = crate_count(&crates);
         if display_authors {
             let crate_authors = crates
                 .iter()
@@ -54,31 +107,80 @@ fn group_by_license_type(
                 .collect::<BTreeSet<_>>();
             println!(
                 "{} ({})\n{}\n{} {}",
-                colored(&license, &Green.bold(), enable_color),
-                crates.len(),
-                crate_names.join(", "),
-                colored("by", &Green.normal(), enable_color),
+                colored(&license, &style, theme),
+                count,
+                wrap_list(&crate_names, 0, wrap),
+                colored("by", &Green.normal(), theme),
                 crate_authors.into_iter().collect::<Vec<_>>().join(", ")
             );
         } else {
+            let prefix_len = format!("{license} ({count}): ").chars().count();
             println!(
                 "{} ({}): {}",
-                colored(&license, &Green.bold(), enable_color),
-                crates.len(),
-                crate_names.join(", ")
+                colored(&license, &style, theme),
+                count,
+                wrap_list(&crate_names, prefix_len, wrap)
             );
         }
     }
 }
 
+/// The number of crates in a license bucket, followed by how many of them
+/// are direct dependencies when that is known.
+fn crate_count(crates: &[DependencyDetails]) -> String {
+    if crates.iter().any(|c| c.is_direct.is_some()) {
+        let direct = crates.iter().filter(|c| c.is_direct == Some(true)).count();
+        format!("{}, {direct} direct", crates.len())
+    } else {
+        crates.len().to_string()
+    }
+}
+
+/// Joins `items` with `", "`, breaking lines before they exceed `width`
+/// columns and indenting continuation lines. `used` is the width already
+/// taken on the first line.
+fn wrap_list(items: &[String], used: usize, width: Option<usize>) -> String {
+    const INDENT: usize = 4;
+    let Some(width) = width.filter(|&width| width > 0) else {
+        return items.join(", ");
+    };
+    let mut output = String::new();
+    let mut line_len = used;
+    let mut line_empty = true;
+    for (i, item) in items.iter().enumerate() {
+        let separator = if i + 1 < items.len() { "," } else { "" };
+        let len = item.chars().count() + separator.len();
+        if !line_empty && line_len + 1 + len > width {
+            output.push('\n');
+            output.push_str(&" ".repeat(INDENT));
+            line_len = INDENT;
+            line_empty = true;
+        }
+        if !line_empty {
+            output.push(' ');
+            line_len += 1;
+        }
+        output.push_str(item);
+        output.push_str(separator);
+        line_len += len;
+        line_empty = false;
+    }
+    output
+}
+
 fn one_license_per_line(
     dependencies: Vec<DependencyDetails>,
     display_authors: bool,
-    enable_color: bool,
+    display_version: bool,
+    theme: Theme,
 ) {
     for dependency in dependencies {
         let name = dependency.name.clone();
-        let version = dependency.version.clone();
+        let version = if display_version {
+            format!(" {},", dependency.version)
+        } else {
+            String::new()
+        };
         let license_file = dependency.license_file.as_ref();
         let license = dependency.license.unwrap_or_else(move || {
             if license_file.is_some() {
@@ -90,17 +192,17 @@ fn one_license_per_line(
         if display_authors {
             let authors = dependency.authors.unwrap_or_else(|| "N/A".to_owned());
             println!(
-                "{}: {}, \"{}\", {}, \"{}\"",
-                colored(&name, &Green.bold(), enable_color),
+                "{}:{} \"{}\", {}, \"{}\"",
+                colored(&name, &Green.bold(), theme),
                 version,
                 license,
-                colored("by", &Green.normal(), enable_color),
+                colored("by", &Green.normal(), theme),
                 authors
             );
         } else {
             println!(
-                "{}: {}, \"{}\",",
-                colored(&name, &Green.bold(), enable_color),
+                "{}:{} \"{}\",",
+                colored(&name, &Green.bold(), theme),
                 version,
                 license,
             );
@@ -108,9 +210,602 @@ fn one_license_per_line(
     }
 }
 
-fn colored<'a>(s: &'a str, style: &Style, enable_color: bool) -> Cow<'a, str> {
-    if enable_color {
-        Cow::Owned(format!("{}", style.paint(s)))
+fn bundle_licenses(
+    dependencies: &[DependencyDetails],
+    normalize_text: bool,
+    theme: Theme,
+) -> Result<()> {
+    let bundle = bundle_license_texts(dependencies, normalize_text)?;
+    for group in &bundle.groups {
+        println!("{}", "=".repeat(80));
+        println!(
+            "{}",
+            colored(
+                "The following crates are licensed under this text:",
+                &Green.bold(),
+                theme
+            )
+        );
+        for krate in &group.crates {
+            if krate.copyright.is_empty() {
+                println!("    {} {}", krate.name, krate.version);
+            } else {
+                println!(
+                    "    {} {} ({})",
+                    krate.name,
+                    krate.version,
+                    krate.copyright.join("; ")
+                );
+            }
+        }
+        println!("\n{}\n", group.text);
+    }
+    if !bundle.missing.is_empty() {
+        println!("{}", "=".repeat(80));
+        println!(
+            "{}",
+            colored(
+                "The following crates ship no license text:",
+                &Green.bold(),
+                theme
+            )
+        );
+        for (name, version) in &bundle.missing {
+            println!("    {name} {version}");
+        }
+    }
+    Ok(())
+}
+
+fn explain_license(id: &str, theme: Theme) -> Result<()> {
+    let Some(info) = LicenseInfo::lookup(id) else {
+        match LicenseInfo::suggest(id) {
+            Some(suggestion) => {
+                bail!("unknown SPDX license identifier `{id}`, did you mean `{suggestion}`?")
+            }
+            None => bail!("unknown SPDX license identifier `{id}`"),
+        }
+    };
+    let yes_no = |b: bool| if b { "yes" } else { "no" };
+    let (plus, or_later) = if info.or_later {
+        ("+", " or later")
+    } else {
+        ("", "")
+    };
+    println!(
+        "{}: {}{or_later}",
+        colored(&format!("{}{plus}", info.id), &Green.bold(), theme),
+        info.full_name
+    );
+    println!("  OSI approved:   {}", yes_no(info.osi_approved));
+    println!("  FSF free/libre: {}", yes_no(info.fsf_free_libre));
+    println!("  Copyleft:       {}", yes_no(info.copyleft));
+    println!("  Deprecated:     {}", yes_no(info.deprecated));
+    println!("  URL:            {}", info.url);
+    Ok(())
+}
+
+fn print_obligations(dependencies: &[DependencyDetails], theme: Theme) {
+    for license in license_obligations(dependencies) {
+        let obligations = if license.obligations.is_empty() {
+            "none".to_owned()
+        } else if license.obligations == [Obligation::ReviewRequired] {
+            colored("review required", &Red.bold(), theme).into_owned()
+        } else {
+            license
+                .obligations
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        println!(
+            "{} ({} crate{}): {obligations}",
+            colored(&license.license, &Green.bold(), theme),
+            license.crates.len(),
+            if license.crates.len() == 1 { "" } else { "s" },
+        );
+    }
+}
+
+fn print_spdx_stats(dependencies: &[DependencyDetails], theme: Theme) {
+    let stats = SpdxStats::of(dependencies);
+    for (label, count, style) in [
+        ("Valid SPDX", stats.valid, Green.bold()),
+        ("License file only", stats.license_file_only, Yellow.bold()),
+        ("Invalid", stats.invalid, Red.bold()),
+        ("Missing", stats.missing, Red.bold()),
+    ] {
+        let count = format!("{count:>5}");
+        println!("{label:<18}{}", colored(&count, &style, theme));
+    }
+}
+
+fn print_who_uses(
+    dependencies: &[DependencyDetails],
+    licensee: &spdx::Licensee,
+    json: bool,
+    theme: Theme,
+) -> Result<()> {
+    let users = who_uses(dependencies, licensee);
+    if json {
+        write_json(users)?;
+        return Ok(());
+    }
+    for dependency in users {
+        println!(
+            "{} {} ({})",
+            colored(&dependency.name, &Style::new().bold(), theme),
+            dependency.version,
+            dependency.license.as_deref().unwrap_or_default(),
+        );
+        if let Some(path) = &dependency.dependency_path {
+            println!("    {}", path.join(" -> "));
+        }
+    }
+    Ok(())
+}
+
+fn print_verdict(
+    dependencies: &[DependencyDetails],
+    project_license: &str,
+    json: bool,
+    theme: Theme,
+) -> Result<()> {
+    let verdict = verdict(dependencies, project_license)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&verdict)?);
+    } else if verdict.passed {
+        println!(
+            "{}: all {} {} may be distributed under {}",
+            colored("PASS", &Green.bold(), theme),
+            dependencies.len(),
+            if dependencies.len() == 1 {
+                "crate"
+            } else {
+                "crates"
+            },
+            verdict.project_license,
+        );
+    } else {
+        println!(
+            "{}: {} {} may not be distributed under {}",
+            colored("FAIL", &Red.bold(), theme),
+            verdict.incompatible.len(),
+            if verdict.incompatible.len() == 1 {
+                "crate"
+            } else {
+                "crates"
+            },
+            verdict.project_license,
+        );
+        for dependency in &verdict.incompatible {
+            println!(
+                "    {} {} ({})",
+                dependency.name,
+                dependency.version,
+                dependency.license.as_deref().unwrap_or("N/A"),
+            );
+        }
+    }
+    if !verdict.passed {
+        bail!(
+            "{} crate(s) are incompatible with `{}`",
+            verdict.incompatible.len(),
+            verdict.project_license
+        );
+    }
+    Ok(())
+}
+
+fn print_minimal_license_set(dependencies: &[DependencyDetails], theme: Theme) {
+    let set = MinimalLicenseSet::of(dependencies);
+    println!(
+        "Choosing {{{}}} satisfies all {} {}",
+        colored(&set.licenses.join(", "), &Green.bold(), theme),
+        set.crates,
+        if set.crates == 1 { "crate" } else { "crates" },
+    );
+    if set.skipped > 0 {
+        println!(
+            "{} {} without a valid SPDX license can't be satisfied",
+            colored(&set.skipped.to_string(), &Red.bold(), theme),
+            if set.skipped == 1 { "crate" } else { "crates" },
+        );
+    }
+}
+
+fn print_author_stats(dependencies: &[DependencyDetails], top: usize, theme: Theme) {
+    let counts = author_counts(dependencies);
+    println!(
+        "{} distinct {} across {} {}",
+        colored(&counts.len().to_string(), &Green.bold(), theme),
+        if counts.len() == 1 {
+            "author"
+        } else {
+            "authors"
+        },
+        colored(&dependencies.len().to_string(), &Green.bold(), theme),
+        if dependencies.len() == 1 {
+            "crate"
+        } else {
+            "crates"
+        },
+    );
+    for count in counts.iter().take(top) {
+        println!("{:>6} {}", count.crates, count.author);
+    }
+}
+
+fn print_category_counts(
+    dependencies: &[DependencyDetails],
+    interpretation: Interpretation,
+    json: bool,
+    theme: Theme,
+) -> Result<()> {
+    let counts = count_by_category(dependencies, interpretation);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&counts)?);
+        return Ok(());
+    }
+    let counts = counts
+        .into_iter()
+        .map(|(category, count)| {
+            let label = format!("{category:?}");
+            format!(
+                "{}: {count}",
+                colored(&label, &category_style(category), theme)
+            )
+        })
+        .collect::<Vec<_>>();
+    println!("{}", counts.join(", "));
+    Ok(())
+}
+
+/// Finds the nearest `Cargo.toml` in the current directory or its parents,
+/// the way cargo does.
+fn find_manifest() -> Result<PathBuf> {
+    let cwd = std::env::current_dir().context("failed to get the current directory")?;
+    cwd.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|path| path.is_file())
+        .with_context(|| {
+            format!(
+                "could not find `Cargo.toml` in `{}` or any parent directory",
+                cwd.display()
+            )
+        })
+}
+
+fn parse_license_expression(license: &str) -> Result<String, String> {
+    spdx::Expression::parse_mode(license, spdx::ParseMode::LAX)
+        .map(|_| license.to_owned())
+        .map_err(|err| err.to_string())
+}
+
+fn parse_remote(spec: &str) -> Result<String, String> {
+    let (name, version) = match spec.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (spec, None),
+    };
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(format!("invalid crate name `{name}`"));
+    }
+    if let Some(version) = version {
+        semver::Version::parse(version).map_err(|err| err.to_string())?;
+    }
+    Ok(spec.to_owned())
+}
+
+/// A temporary project depending on nothing but a crate from crates.io,
+/// removed when dropped.
+struct RemoteProject {
+    dir: PathBuf,
+}
+
+impl RemoteProject {
+    /// Creates the project for `spec`, `name` or `name@version`.
+    fn create(spec: &str) -> Result<Self> {
+        let (name, requirement) = match spec.split_once('@') {
+            Some((name, version)) => (name, format!("={version}")),
+            None => (spec, "*".to_owned()),
+        };
+        let project = Self {
+            dir: create_temp_dir()?,
+        };
+        fs::create_dir(project.dir.join("src"))?;
+        fs::write(project.dir.join("src").join("lib.rs"), "")?;
+        fs::write(
+            project.manifest_path(),
+            format!(
+                "[package]\nname = \"cargo-license-remote\"\nversion = \"0.0.0\"\n\
+                 edition = \"2021\"\npublish = false\n\n[dependencies]\n\
+                 {name} = \"{requirement}\"\n\n[workspace]\n"
+            ),
+        )?;
+        Ok(project)
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        self.dir.join("Cargo.toml")
+    }
+}
+
+/// Creates a new directory with a random name in the temporary directory,
+/// never reusing one that already exists.
+fn create_temp_dir() -> Result<PathBuf> {
+    let mut attempts = 0;
+    loop {
+        // `RandomState` is randomly seeded for every process.
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u32(attempts);
+        let dir =
+            std::env::temp_dir().join(format!("cargo-license-remote-{:016x}", hasher.finish()));
+        match fs::create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists && attempts < 16 => {
+                attempts += 1;
+            }
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed to create `{}`", dir.display()))
+            }
+        }
+    }
+}
+
+impl Drop for RemoteProject {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn parse_date(date: &str) -> Result<String, String> {
+    if is_valid_date(date) {
+        Ok(date.to_owned())
+    } else {
+        Err("expected a valid date in the YYYY-MM-DD format".to_owned())
+    }
+}
+
+fn parse_licensee(license: &str) -> Result<spdx::Licensee, String> {
+    spdx::Licensee::parse(license).map_err(|err| err.to_string())
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn write_license_files(
+    dependencies: &[DependencyDetails],
+    output_dir: &Path,
+    log_format: LogFormat,
+) -> Result<()> {
+    fs::create_dir_all(output_dir)
+        .with_context(|| format!("failed to create `{}`", output_dir.display()))?;
+    let mut written = 0;
+    for dependency in dependencies {
+        let texts = dependency.license_texts()?;
+        if texts.is_empty() {
+            log_format.log(
+                "warning",
+                &format!(
+                    "`{} {}` ships no license text",
+                    dependency.name, dependency.version
+                ),
+                json!({ "crate": dependency.name, "version": dependency.version }),
+            );
+            continue;
+        }
+        let contents = texts
+            .iter()
+            .map(|text| {
+                format!(
+                    "==> {} <==\n\n{}\n",
+                    text.path.file_name().unwrap_or_default(),
+                    text.text.trim_end()
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let path = output_dir.join(sanitize_file_name(&format!(
+            "{}-{}.txt",
+            dependency.name, dependency.version
+        )));
+        fs::write(&path, contents)
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+        written += 1;
+    }
+    log_format.log(
+        "note",
+        &format!(
+            "wrote {written} license file(s) to `{}`",
+            output_dir.display()
+        ),
+        json!({ "count": written, "path": output_dir }),
+    );
+    Ok(())
+}
+
+fn print_diff(diff: &ReportDiff, theme: Theme) {
+    let license = |d: &DependencyDetails| d.license.as_deref().unwrap_or("N/A").to_owned();
+    let describe = |d: &DependencyDetails| format!("{} {} ({})", d.name, d.version, license(d));
+    for dependency in &diff.removed {
+        let line = format!("- {}", describe(dependency));
+        println!("{}", colored(&line, &Red.normal(), theme));
+    }
+    for dependency in &diff.added {
+        let line = format!("+ {}", describe(dependency));
+        println!("{}", colored(&line, &Green.normal(), theme));
+    }
+    for (old, new) in &diff.changed {
+        let version = if old.version == new.version {
+            new.version.to_string()
+        } else {
+            format!("{} -> {}", old.version, new.version)
+        };
+        let (old_license, new_license) = (license(old), license(new));
+        let license = if old_license == new_license {
+            new_license
+        } else {
+            format!(
+                "{} -> {}",
+                colored(&old_license, &Red.normal(), theme),
+                colored(&new_license, &Green.normal(), theme)
+            )
+        };
+        println!(
+            "{} {} {version} ({license})",
+            colored("~", &Yellow.normal(), theme),
+            new.name
+        );
+    }
+}
+
+fn check_license_hash(
+    path: &Path,
+    dependencies: &[DependencyDetails],
+    update: bool,
+    log_format: LogFormat,
+) -> Result<()> {
+    let hash = license_hash(dependencies);
+    if update {
+        fs::write(path, format!("{hash}\n"))
+            .with_context(|| format!("failed to write `{}`", path.display()))?;
+        log_format.log(
+            "note",
+            &format!("wrote license hash to `{}`", path.display()),
+            json!({ "path": path, "hash": hash }),
+        );
+        return Ok(());
+    }
+    if !path.exists() {
+        bail!(
+            "`{}` does not exist; create it with `--update-license-hash`",
+            path.display()
+        );
+    }
+    let expected =
+        fs::read_to_string(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+    if expected.trim() != hash {
+        bail!(
+            "the licensed dependencies changed; review them and update `{}` with \
+             `--update-license-hash`",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+fn check_consistency(dependencies: &[DependencyDetails]) -> Vec<Warning> {
+    dependencies
+        .iter()
+        .filter_map(|dependency| {
+            let license = dependency.license.as_ref()?;
+            let license_file = dependency.license_file.as_ref()?;
+            Some(Warning::new(
+                WarningKind::LicenseConsistency,
+                format!(
+                    "`{} {}` declares both license `{}` and license file `{}`; \
+                     verify that they match",
+                    dependency.name, dependency.version, license, license_file
+                ),
+            ))
+        })
+        .collect()
+}
+
+fn format_field(dependency: &DependencyDetails, field: &str) -> Result<String> {
+    let value = match field {
+        "name" => Some(dependency.name.clone()),
+        "version" => Some(dependency.version.to_string()),
+        "authors" => dependency.authors.clone(),
+        "repository" => dependency.repository.clone(),
+        "license" => dependency.license.clone(),
+        "license_file" => dependency.license_file.clone(),
+        "description" => dependency.description.clone(),
+        _ => bail!("unknown placeholder `{{{field}}}` in format string"),
+    };
+    Ok(value.unwrap_or_default())
+}
+
+/// Renders `template`, replacing `{field}` placeholders with the matching
+/// dependency fields. `{{` and `}}` produce literal braces.
+fn render_format_string(template: &str, dependency: &DependencyDetails) -> Result<String> {
+    let mut output = String::new();
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.next_if_eq(&'{').is_some() => output.push('{'),
+            '{' => {
+                let mut field = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(c) => field.push(c),
+                        None => bail!("unclosed `{{` in format string"),
+                    }
+                }
+                output.push_str(&format_field(dependency, &field)?);
+            }
+            '}' => {
+                if chars.next() != Some('}') {
+                    bail!("unmatched `}}` in format string");
+                }
+                output.push('}');
+            }
+            _ => output.push(c),
+        }
+    }
+    Ok(output)
+}
+
+fn write_format_string(dependencies: &[DependencyDetails], template: &str) -> Result<()> {
+    for dependency in dependencies {
+        println!("{}", render_format_string(template, dependency)?);
+    }
+    Ok(())
+}
+
+/// How the human-readable output is colored.
+#[derive(Debug, Clone, Copy)]
+struct Theme {
+    enabled: bool,
+    /// Swap the colors that are hard to read on a light background for
+    /// darker ones.
+    light: bool,
+    bold: bool,
+}
+
+impl Theme {
+    fn adapt(self, style: &Style) -> Style {
+        let mut style = *style;
+        if self.light {
+            style.foreground = style.foreground.map(|colour| match colour {
+                Green => Fixed(28),
+                Yellow => Fixed(130),
+                colour => colour,
+            });
+        }
+        style.is_bold &= self.bold;
+        style
+    }
+}
+
+fn colored<'a>(s: &'a str, style: &Style, theme: Theme) -> Cow<'a, str> {
+    if theme.enabled {
+        Cow::Owned(format!("{}", theme.adapt(style).paint(s)))
     } else {
         Cow::Borrowed(s)
     }
@@ -130,35 +825,252 @@ fn colored<'a>(s: &'a str, style: &Style, enable_color: bool) -> Cow<'a, str> {
         .placeholder(AnsiColor::Green.on_default())
     )
 )]
+#[clap(group(
+    ArgGroup::new("output_format")
+        .args(["format", "do_not_bundle", "tsv", "json", "json_grouped", "toml", "gitlab", "ort", "syft"])
+        .multiple(false)
+))]
 struct Opt {
     #[clap(value_name = "PATH", long, display_order(0))]
-    /// Path to Cargo.toml.
-    manifest_path: Option<PathBuf>,
+    /// Path to Cargo.toml. May be repeated to merge the reports of several projects.
+    manifest_path: Vec<PathBuf>,
 
     #[clap(value_name = "CURRENT_DIR", long, display_order(0))]
     /// Current directory of the cargo metadata process.
     current_dir: Option<PathBuf>,
 
+    #[clap(
+        long,
+        value_name = "NAME[@VERSION]",
+        value_parser = parse_remote,
+        conflicts_with_all = ["manifest_path", "current_dir", "locked", "frozen"],
+        display_order(0)
+    )]
+    /// Report the licenses of a crate from crates.io and its dependencies without a local
+    /// project, e.g. `serde@1.0.200`. The latest version is used when VERSION is omitted. Needs
+    /// network access to resolve the crate.
+    remote: Option<String>,
+
+    #[clap(short, long, value_name = "NAME", display_order(0))]
+    /// Only report the dependencies of this workspace member.
+    package: Option<String>,
+
+    #[clap(
+        long = "exclude-workspace-member",
+        value_name = "NAME",
+        conflicts_with = "package",
+        display_order(0)
+    )]
+    /// Leave a workspace member out of the roots, along with the crates only it depends on. May
+    /// be repeated.
+    exclude_workspace_member: Vec<String>,
+
     #[clap(short, long, display_order(0))]
     /// Display crate authors
     authors: bool,
 
+    #[clap(long = "dedupe-authors", display_order(0))]
+    /// Canonicalize authors to `Name <email>` and drop duplicates of the same person.
+    dedupe_authors: bool,
+
+    #[clap(long = "vendor-dir", value_name = "DIR", display_order(0))]
+    /// Read the license files of registry and git dependencies from a directory created by
+    /// `cargo vendor`.
+    vendor_dir: Option<Utf8PathBuf>,
+
+    #[clap(long = "normalize-repos", display_order(0))]
+    /// Canonicalize repository URLs to `https://` without a `.git` suffix.
+    normalize_repos: bool,
+
+    #[clap(
+        long = "author-separator",
+        value_name = "STR",
+        conflicts_with = "author_stats",
+        display_order(0)
+    )]
+    /// Separate the authors of a crate with STR instead of `|` in the output.
+    author_separator: Option<String>,
+
+    #[clap(long = "no-authors", overrides_with = "authors", display_order(0))]
+    /// Hide crate authors, overriding `--authors`.
+    no_authors: bool,
+
+    #[clap(long = "no-version", display_order(0))]
+    /// Hide crate versions in the one-license-per-line output.
+    no_version: bool,
+
     #[clap(short, long, display_order(0))]
-    /// Output one license per line.
+    /// Output one license per line. Deprecated, use `--format do-not-bundle`.
     do_not_bundle: bool,
 
+    #[clap(long = "missing-only", display_order(0))]
+    /// Only output crates with neither a license nor a license file, with their repository.
+    missing_only: bool,
+
+    #[clap(
+        long = "show-root-license",
+        conflicts_with_all = ["tsv", "json", "json_grouped", "toml", "gitlab", "ort", "syft"],
+        display_order(0)
+    )]
+    /// Print the license of every workspace member before the output.
+    show_root_license: bool,
+
+    #[clap(
+        long = "assume-license-file",
+        value_name = "SPDX",
+        value_parser = parse_license_expression,
+        display_order(0)
+    )]
+    /// Treat crates that only have a license file as under this license, instead of `Custom
+    /// License File`, in the output, the categories and the policy checks.
+    assume_license_file: Option<String>,
+
+    #[clap(long = "licenses-only", display_order(0))]
+    /// Output the distinct licenses, one per line, without their crates.
+    licenses_only: bool,
+
+    #[clap(long, display_order(0))]
+    /// List the obligations of every distinct license.
+    obligations: bool,
+
+    #[clap(long = "spdx-stats", display_order(0))]
+    /// Count the crates with a valid SPDX license, only a license file, an invalid license or
+    /// nothing.
+    spdx_stats: bool,
+
+    #[clap(long = "who-uses", value_name = "LICENSE", value_parser = parse_licensee, display_order(0))]
+    /// List the crates under a license with the dependency path that pulls each of them in, as
+    /// JSON with `--json`.
+    who_uses: Option<spdx::Licensee>,
+
+    #[clap(long = "minimal-license-set", display_order(0))]
+    /// Find a small set of licenses satisfying the license of every crate, choosing among `OR`
+    /// alternatives.
+    minimal_license_set: bool,
+
+    #[clap(long = "verdict", display_order(0))]
+    /// Check that every crate may be distributed under the project's license, failing with the
+    /// incompatible crates otherwise, as JSON with `--json`.
+    verdict: bool,
+
+    #[clap(
+        long = "project-license",
+        value_name = "SPDX",
+        value_parser = parse_license_expression,
+        requires = "verdict",
+        display_order(0)
+    )]
+    /// The project's license for `--verdict`, instead of the license of the root package.
+    project_license: Option<String>,
+
+    #[clap(long = "author-stats", display_order(0))]
+    /// Count the distinct authors across all crates.
+    author_stats: bool,
+
+    #[clap(long = "count-by-category", display_order(0))]
+    /// Count the crates in every license category, as a JSON object with `--json`.
+    count_by_category: bool,
+
+    #[clap(
+        long = "top-authors",
+        value_name = "N",
+        requires = "author_stats",
+        default_value = "0",
+        display_order(0)
+    )]
+    /// List the N authors of the most crates with `--author-stats`.
+    top_authors: usize,
+
+    #[clap(long = "bundle-licenses", display_order(0))]
+    /// Output every unique license text once, followed by the crates using it.
+    bundle_licenses: bool,
+
+    #[clap(
+        long = "output-dir",
+        value_name = "DIR",
+        requires = "bundle_licenses",
+        display_order(0)
+    )]
+    /// Write the license texts of every crate to `<DIR>/<crate>-<version>.txt`.
+    output_dir: Option<PathBuf>,
+
+    #[clap(
+        long = "normalize-license-text",
+        requires = "bundle_licenses",
+        display_order(0)
+    )]
+    /// Merge license texts only differing in whitespace or copyright lines.
+    normalize_license_text: bool,
+
+    #[clap(long, display_order(0))]
+    /// Print the number of crates and distinct licenses before the grouped output.
+    stats: bool,
+
+    #[clap(long, value_name = "WIDTH", display_order(0))]
+    /// Wrap the crate lists of the grouped output to WIDTH columns, 0 to disable [default: the
+    /// terminal width].
+    wrap: Option<usize>,
+
+    #[clap(long = "group-threshold", value_name = "N", display_order(0))]
+    /// Collapse permissive licenses used by fewer than N crates into an "Other" group in the
+    /// grouped output. Copyleft, missing and custom licenses are never collapsed.
+    group_threshold: Option<usize>,
+
     #[clap(short, long, display_order(0))]
-    /// Detailed output as tab-separated-values.
+    /// Detailed output as tab-separated-values. Deprecated, use `--format tsv`.
     tsv: bool,
 
     #[clap(short, long, display_order(0))]
-    /// Detailed output as JSON.
+    /// Detailed output as JSON. Deprecated as an output format, use `--format json`; still
+    /// selects JSON for `--who-uses`, `--verdict` and `--count-by-category`.
     json: bool,
 
+    #[clap(long = "json-envelope", display_order(0))]
+    /// Wrap the JSON output in an object with `warnings` and `dependencies` arrays, the
+    /// `generated_at` time and the `tool_version` of cargo-license.
+    json_envelope: bool,
+
+    #[clap(long = "print-schema", display_order(0))]
+    /// Print the JSON Schema of the `--json` output and exit.
+    print_schema: bool,
+
+    #[clap(long = "json-grouped", display_order(0))]
+    /// Detailed output as a JSON object mapping every license to its crates. Deprecated, use
+    /// `--format json-grouped`.
+    json_grouped: bool,
+
+    #[clap(long, display_order(0))]
+    /// Detailed output as TOML. Deprecated, use `--format toml`.
+    toml: bool,
+
     #[clap(short, long, display_order(0))]
-    /// Gitlab license scanner output
+    /// Gitlab license scanner output. Deprecated, use `--format gitlab`.
     gitlab: bool,
 
+    #[clap(long, display_order(0))]
+    /// OSS Review Toolkit (ORT) analyzer result with the packages only. Deprecated, use
+    /// `--format ort`.
+    ort: bool,
+
+    #[clap(long, display_order(0))]
+    /// Syft JSON document with the crates as `rust-crate` packages. Deprecated, use
+    /// `--format syft`.
+    syft: bool,
+
+    #[clap(long, value_enum, value_name = "FORMAT", display_order(0))]
+    /// Output format, replacing the individual format flags [default: grouped, or
+    /// `CARGO_LICENSE_FORMAT`].
+    format: Option<OutputFormat>,
+
+    #[clap(long = "format-string", value_name = "TEMPLATE", display_order(0))]
+    /// Output each crate using a template such as "{name},{version},{license}".
+    format_string: Option<String>,
+
+    #[clap(long, value_name = "FILE", display_order(0))]
+    /// Render the crates through a handlebars template, which gets a `dependencies` array and a
+    /// `licenses` object mapping every license to its crates.
+    template: Option<PathBuf>,
+
     #[clap(long, display_order(0))]
     /// Exclude development dependencies
     avoid_dev_deps: bool,
@@ -167,34 +1079,283 @@ struct Opt {
     /// Exclude build dependencies
     avoid_build_deps: bool,
 
+    #[clap(long, conflicts_with = "avoid_build_deps", display_order(0))]
+    /// Only include crates reachable exclusively through build dependencies.
+    build_deps_only: bool,
+
     #[clap(long, display_order(0))]
     /// Exclude `proc_macros` dependencies
     avoid_proc_macros: bool,
 
-    #[clap(long = "features", value_name = "FEATURE", display_order(0))]
-    /// Space-separated list of features to activate.
-    features: Option<Vec<String>>,
-
-    #[clap(long = "all-features", display_order(0))]
-    /// Activate all available features.
-    all_features: bool,
+    #[clap(
+        long = "dep-kinds",
+        value_name = "KIND",
+        value_enum,
+        value_delimiter = ',',
+        conflicts_with_all = ["avoid_dev_deps", "avoid_build_deps"],
+        display_order(0)
+    )]
+    /// Comma-separated list of dependency kinds to include.
+    dep_kinds: Option<Vec<DepKind>>,
 
-    #[clap(long = "no-default-features", display_order(0))]
-    /// Deactivate default features
-    no_default_features: bool,
+    #[clap(long = "include-kinds", display_order(0))]
+    /// Include the dependency kinds through which every crate is reachable in the detailed
+    /// output.
+    include_kinds: bool,
 
-    #[clap(long = "direct-deps-only", display_order(0))]
+    #[clap(
+        long,
+        value_name = "KIND",
+        value_enum,
+        value_delimiter = ',',
+        display_order(0)
+    )]
+    /// Only output crates reachable through one of the given dependency kinds, e.g. `--kind dev`
+    /// for crates used by tests.
+    kind: Vec<DepKind>,
+
+    #[clap(long = "hash-license-files", display_order(0))]
+    /// Include the SHA-256 of every crate's license file in the detailed output.
+    hash_license_files: bool,
+
+    #[clap(
+        long = "license-file-paths",
+        value_enum,
+        value_name = "STYLE",
+        default_value = "raw",
+        display_order(0)
+    )]
+    /// How to report `license_file` paths.
+    license_file_paths: LicenseFilePathsArg,
+
+    #[clap(
+        long = "sort-by",
+        value_enum,
+        value_name = "ORDER",
+        default_value = "name",
+        display_order(0)
+    )]
+    /// The order of the crates in the output. `topo` lists every crate before its dependencies.
+    sort_by: SortByArg,
+
+    #[clap(
+        long = "relative-paths",
+        conflicts_with = "license_file_paths",
+        display_order(0)
+    )]
+    /// Report paths relative to the workspace root, same as
+    /// `--license-file-paths workspace-relative`.
+    relative_paths: bool,
+
+    #[clap(long = "features", value_name = "FEATURE", display_order(0))]
+    /// Space or comma-separated list of features to activate.
+    features: Option<Vec<String>>,
+
+    #[clap(long = "all-features", display_order(0))]
+    /// Activate all available features.
+    all_features: bool,
+
+    #[clap(long = "no-default-features", display_order(0))]
+    /// Deactivate default features
+    no_default_features: bool,
+
+    #[clap(long = "direct-deps-only", display_order(0))]
     /// Output information only about the root package and don't fetch dependencies.
     direct_deps_only: bool,
 
-    #[clap(long = "root-only", display_order(0))]
-    /// Output information only about the root package.
+    #[clap(long = "root-only", visible_alias = "no-deps", display_order(0))]
+    /// Output information only about the root package. `--no-deps` is an alias kept for
+    /// compatibility with older versions.
     root_only: bool,
 
+    #[clap(long, value_name = "PATTERN", display_order(0))]
+    /// Leave crates whose name matches this glob pattern out of the output.
+    exclude: Vec<String>,
+
+    #[clap(
+        long = "include-license-match",
+        value_name = "PATTERN",
+        display_order(0)
+    )]
+    /// Only output crates whose license matches this glob pattern, e.g. `Apache-*`.
+    include_license_match: Vec<String>,
+
+    #[clap(
+        long = "exclude-license-match",
+        value_name = "PATTERN",
+        display_order(0)
+    )]
+    /// Leave crates whose license matches this glob pattern out of the output.
+    exclude_license_match: Vec<String>,
+
+    #[clap(long = "exclude-license", value_name = "LICENSE", display_order(0))]
+    /// Leave crates that can be used under this license out of the output, e.g. `MIT` also
+    /// leaves out `MIT OR Apache-2.0`.
+    exclude_license: Vec<String>,
+
+    #[clap(long = "exclude-file", value_name = "PATH", display_order(0))]
+    /// File with one crate name glob pattern per line to leave out of the output.
+    exclude_file: Option<PathBuf>,
+
+    #[clap(long = "max-depth", value_name = "N", display_order(0))]
+    /// Only include dependencies up to N levels below the root package.
+    max_depth: Option<usize>,
+
+    #[clap(long = "no-root", visible_alias = "third-party-only", display_order(0))]
+    /// Exclude the root package and workspace members from the output.
+    no_root: bool,
+
+    #[clap(long = "ignore-private", display_order(0))]
+    /// Exclude packages with `publish = false` from the output.
+    ignore_private: bool,
+
+    #[clap(long = "check-consistency", display_order(0))]
+    /// Report crates that declare both a license and a license file.
+    check_consistency: bool,
+
+    #[clap(long = "check-compatibility", display_order(0))]
+    /// Report crates whose license combines licenses known to be incompatible.
+    check_compatibility: bool,
+
+    #[clap(long, display_order(0))]
+    /// Require Cargo.lock to be up to date and never create or update it, passed through to
+    /// cargo metadata.
+    locked: bool,
+
+    #[clap(long, display_order(0))]
+    /// Require Cargo.lock and cache to be up to date, passed through to cargo metadata.
+    frozen: bool,
+
+    #[clap(long, display_order(0))]
+    /// Exit with an error if any warning was emitted.
+    strict: bool,
+
+    #[clap(long = "max-crates", value_name = "N", display_order(0))]
+    /// Exit with an error, without writing the report, if more than N crates were resolved.
+    max_crates: Option<usize>,
+
+    #[clap(long, value_name = "PATH", display_order(0))]
+    /// TOML file mapping crate names to licenses overriding the declared ones.
+    overrides: Option<PathBuf>,
+
+    #[clap(long, value_name = "PATH", display_order(0))]
+    /// TOML file mapping crate names to lists of tags reported with the crates.
+    tags: Option<PathBuf>,
+
+    #[clap(long = "deny-config", value_name = "PATH", display_order(0))]
+    /// Check licenses against the `allow`, `deny` and `exceptions` rules of a cargo-deny config.
+    deny_config: Option<PathBuf>,
+
+    #[clap(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        default_missing_value = ".license-hash",
+        display_order(0)
+    )]
+    /// Fail if the hash of the dependencies and their licenses differs from the one in PATH
+    /// [default: .license-hash], or if PATH does not exist.
+    fail_on_change: Option<PathBuf>,
+
+    #[clap(
+        long = "update-license-hash",
+        requires = "fail_on_change",
+        display_order(0)
+    )]
+    /// Write the current hash to the `--fail-on-change` file instead of checking it.
+    update_license_hash: bool,
+
+    #[clap(long, value_name = "PATH", display_order(0))]
+    /// Compare the report with a JSON file written by `--json` and fail if they differ.
+    verify: Option<PathBuf>,
+
+    #[clap(long = "require-election", display_order(0))]
+    /// Fail if a crate offers a license choice not resolved by `--overrides`.
+    require_election: bool,
+
+    #[clap(long = "fail-fast", display_order(0))]
+    /// Stop at the first license policy violation, without writing the report, instead of
+    /// reporting every violation.
+    fail_fast: bool,
+
+    #[clap(long = "osi-only", display_order(0))]
+    /// Fail if a crate's license is not OSI-approved. An `OR` expression passes if any choice is.
+    osi_only: bool,
+
+    #[clap(long = "fsf-only", display_order(0))]
+    /// Fail if a crate's license is not FSF free/libre. An `OR` expression passes if any choice
+    /// is.
+    fsf_only: bool,
+
+    #[clap(long = "no-deprecated-licenses", display_order(0))]
+    /// Fail if a crate's license uses a deprecated SPDX identifier, such as `GPL-3.0`.
+    no_deprecated_licenses: bool,
+
+    #[clap(long = "policy-json", display_order(0))]
+    /// Output the license policy check result as JSON instead of the report.
+    policy_json: bool,
+
+    #[clap(long = "best-case", overrides_with = "worst_case", display_order(0))]
+    /// Assume the most permissive license of `OR` expressions is chosen when categorizing and
+    /// checking licenses. This is the default.
+    best_case: bool,
+
+    #[clap(long = "worst-case", overrides_with = "best_case", display_order(0))]
+    /// Require every license of `OR` expressions to be acceptable when categorizing and checking
+    /// licenses.
+    worst_case: bool,
+
     #[clap(long = "filter-platform", value_name = "TRIPLE", display_order(0))]
-    /// Only include resolve dependencies matching the given target-triple.
+    /// Only include resolve dependencies matching the given target-triple [default: the
+    /// `build.target` of cargo's configuration, if any].
     filter_platform: Option<String>,
 
+    #[clap(long, value_name = "LICENSE", display_order(0))]
+    /// Describe an SPDX license identifier and exit.
+    explain: Option<String>,
+
+    #[clap(long = "include-graph-stats", display_order(0))]
+    /// Include the number of dependents of every crate in the detailed output.
+    include_graph_stats: bool,
+
+    #[clap(long = "include-transitive-count", display_order(0))]
+    /// Show how many crates of every license are direct dependencies.
+    include_transitive_count: bool,
+
+    #[clap(long = "include-features", display_order(0))]
+    /// Include the resolved features of every crate in the detailed output.
+    include_features: bool,
+
+    #[clap(long = "no-normalize-sort", display_order(0))]
+    /// Keep the terms of license expressions in their declared order instead of sorting them.
+    no_normalize_sort: bool,
+
+    #[clap(long, display_order(0))]
+    /// Output a single row per crate name and version, warning about duplicates with different
+    /// details.
+    unique: bool,
+
+    #[clap(long = "include-metadata", display_order(0))]
+    /// Include the edition and MSRV of every crate in the detailed output.
+    include_metadata: bool,
+
+    #[clap(long = "check-yanked", display_order(0))]
+    /// Flag yanked crates.io versions using cargo's local registry index cache.
+    check_yanked: bool,
+
+    #[clap(long, value_name = "YYYY-MM-DD", value_parser = parse_date, display_order(0))]
+    /// Flag crates.io versions published before the given date, using cargo's local registry
+    /// index cache.
+    since: Option<String>,
+
+    #[clap(long, display_order(0))]
+    /// Show a progress bar on stderr while collecting crates.
+    progress: bool,
+
+    #[clap(long, display_order(0))]
+    /// Print the duration of every phase to stderr.
+    timings: bool,
+
     #[clap(
         long = "color",
         name = "WHEN",
@@ -204,6 +1365,73 @@ struct Opt {
     )]
     /// Coloring
     color: Color,
+
+    #[clap(long, value_enum, default_value = "dark", display_order(0))]
+    /// Colors readable on a dark or a light terminal background.
+    theme: ThemeArg,
+
+    #[clap(long = "no-bold", display_order(0))]
+    /// Don't use bold text in colored output.
+    no_bold: bool,
+
+    #[clap(
+        long = "log-format",
+        value_enum,
+        value_name = "FORMAT",
+        default_value = "human",
+        display_order(0)
+    )]
+    /// Format of the warnings, policy violations and errors printed to stderr.
+    log_format: LogFormat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum DepKind {
+    Normal,
+    Dev,
+    Build,
+}
+
+impl From<DepKind> for DependencyKind {
+    fn from(kind: DepKind) -> Self {
+        match kind {
+            DepKind::Normal => DependencyKind::Normal,
+            DepKind::Dev => DependencyKind::Development,
+            DepKind::Build => DependencyKind::Build,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum LicenseFilePathsArg {
+    Raw,
+    Absolute,
+    WorkspaceRelative,
+}
+
+impl From<LicenseFilePathsArg> for LicenseFilePaths {
+    fn from(paths: LicenseFilePathsArg) -> Self {
+        match paths {
+            LicenseFilePathsArg::Raw => LicenseFilePaths::Raw,
+            LicenseFilePathsArg::Absolute => LicenseFilePaths::Absolute,
+            LicenseFilePathsArg::WorkspaceRelative => LicenseFilePaths::WorkspaceRelative,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum SortByArg {
+    Name,
+    Topo,
+}
+
+impl From<SortByArg> for SortOrder {
+    fn from(sort_by: SortByArg) -> Self {
+        match sort_by {
+            SortByArg::Name => SortOrder::Name,
+            SortByArg::Topo => SortOrder::Topological,
+        }
+    }
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
@@ -213,78 +1441,807 @@ enum Color {
     Never,
 }
 
-fn run() -> Result<()> {
-    use std::env;
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum OutputFormat {
+    /// Crates grouped by license.
+    #[value(alias = "human")]
+    Grouped,
+    /// One license per line.
+    #[value(alias = "human-oneline")]
+    DoNotBundle,
+    Tsv,
+    Json,
+    JsonGrouped,
+    Toml,
+    Gitlab,
+    Ort,
+    Syft,
+}
 
-    // Drop extra `license` argument when called by `cargo`.
-    let args = env::args().enumerate().filter_map(|(i, x)| {
-        if (i, x.as_str()) == (1, "license") {
-            None
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum LogFormat {
+    Human,
+    /// One JSON object per line.
+    Json,
+}
+
+impl LogFormat {
+    /// Prints a diagnostic to stderr, as `level: message` or as a JSON line
+    /// with the `level`, the `message` and the extra `fields`.
+    fn log(self, level: &str, message: &str, fields: serde_json::Value) {
+        match self {
+            LogFormat::Human => eprintln!("{level}: {message}"),
+            LogFormat::Json => {
+                let mut line = json!({ "level": level, "message": message });
+                if let (Some(line), serde_json::Value::Object(fields)) =
+                    (line.as_object_mut(), fields)
+                {
+                    line.extend(fields);
+                }
+                eprintln!("{line}");
+            }
+        }
+    }
+
+    /// Prints how long a phase took.
+    fn log_timing(self, phase: &str, elapsed: Duration) {
+        self.log(
+            "timing",
+            &format!("{phase}: {elapsed:.2?}"),
+            json!({ "phase": phase, "seconds": elapsed.as_secs_f64() }),
+        );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum, Debug)]
+enum ThemeArg {
+    Dark,
+    Light,
+}
+
+impl Opt {
+    fn interpretation(&self) -> Interpretation {
+        if self.worst_case {
+            Interpretation::WorstCase
         } else {
-            Some(x)
+            Interpretation::BestCase
         }
-    });
+    }
 
-    let opt = Opt::parse_from(args);
-    let mut cmd = MetadataCommand::new();
+    fn enable_color(&self) -> bool {
+        match self.color {
+            Color::Auto => io::stdout().is_terminal(),
+            Color::Always => true,
+            Color::Never => false,
+        }
+    }
 
-    if let Some(path) = &opt.manifest_path {
-        cmd.manifest_path(path);
+    fn theme(&self) -> Theme {
+        Theme {
+            enabled: self.enable_color(),
+            light: self.theme == ThemeArg::Light,
+            bold: !self.no_bold,
+        }
     }
-    if let Some(dir) = &opt.current_dir {
-        cmd.current_dir(dir);
+
+    /// The `--filter-platform` triple, defaulting to the `build.target` of
+    /// cargo's configuration for the project.
+    fn filter_platform(&self, manifest_path: Option<&PathBuf>) -> Result<Option<String>> {
+        if let Some(triple) = &self.filter_platform {
+            return Ok(Some(triple.clone()));
+        }
+        let dir = match (manifest_path, &self.current_dir) {
+            (Some(path), _) => path.parent().map(Path::to_path_buf).unwrap_or_default(),
+            (None, Some(dir)) => dir.clone(),
+            (None, None) => PathBuf::new(),
+        };
+        let dir = std::env::current_dir()?.join(dir);
+        configured_build_target(&dir).context("failed to read cargo's configuration")
     }
-    if opt.all_features {
-        cmd.features(CargoOpt::AllFeatures);
+
+    fn metadata_command(&self, manifest_path: Option<&PathBuf>) -> Result<MetadataCommand> {
+        let mut cmd = MetadataCommand::new();
+
+        let discovered;
+        let manifest_path = match (manifest_path, &self.current_dir) {
+            (None, None) => {
+                discovered = find_manifest()?;
+                Some(&discovered)
+            }
+            _ => manifest_path,
+        };
+
+        if let Some(path) = manifest_path {
+            cmd.manifest_path(path);
+        }
+        if let Some(dir) = &self.current_dir {
+            cmd.current_dir(dir);
+        }
+        if self.all_features {
+            cmd.features(CargoOpt::AllFeatures);
+        }
+        if self.no_default_features {
+            cmd.features(CargoOpt::NoDefaultFeatures);
+        }
+        if let Some(features) = &self.features {
+            let features = features
+                .iter()
+                .flat_map(|f| f.split(|c: char| c == ',' || c.is_whitespace()))
+                .filter(|f| !f.is_empty())
+                .map(str::to_owned)
+                .collect();
+            cmd.features(CargoOpt::SomeFeatures(features));
+        }
+        let mut other_options = Vec::new();
+        if let Some(triple) = self.filter_platform(manifest_path)? {
+            other_options.extend(["--filter-platform".into(), triple]);
+        }
+        if self.locked {
+            other_options.push("--locked".into());
+        }
+        if self.frozen {
+            other_options.push("--frozen".into());
+        }
+        cmd.other_options(other_options);
+        Ok(cmd)
     }
-    if opt.no_default_features {
-        cmd.features(CargoOpt::NoDefaultFeatures);
+
+    /// The license `--verdict` checks against: `--project-license`, or the
+    /// license of the `--package` member or the root package.
+    fn project_license(&self) -> Result<String> {
+        if let Some(license) = &self.project_license {
+            return Ok(license.clone());
+        }
+        let metadata = self
+            .metadata_command(self.manifest_path.first())?
+            .no_deps()
+            .exec()?;
+        let package = match &self.package {
+            Some(name) => metadata
+                .workspace_packages()
+                .into_iter()
+                .find(|package| &package.name == name),
+            None => metadata.root_package(),
+        };
+        let Some(package) = package else {
+            bail!("no root package to detect the project license from, pass `--project-license`");
+        };
+        package.license.clone().with_context(|| {
+            format!(
+                "`{}` has no SPDX license expression, pass `--project-license`",
+                package.name
+            )
+        })
     }
-    if let Some(features) = opt.features {
-        cmd.features(CargoOpt::SomeFeatures(features));
+
+    /// Prints the license of every workspace member of every
+    /// `--manifest-path`, ahead of the report.
+    fn print_root_licenses(&self, theme: Theme) -> Result<()> {
+        let manifest_paths = if self.manifest_path.is_empty() {
+            vec![None]
+        } else {
+            self.manifest_path.iter().map(Some).collect()
+        };
+        for manifest_path in manifest_paths {
+            let metadata = self.metadata_command(manifest_path)?.no_deps().exec()?;
+            for package in metadata.workspace_packages() {
+                let license = match (&package.license, &package.license_file) {
+                    (Some(license), _) => license.as_str(),
+                    (None, Some(_)) => "Custom License File",
+                    (None, None) => "N/A",
+                };
+                let style = category_style(LicenseCategory::of_expression_with(
+                    license,
+                    self.interpretation(),
+                ));
+                println!(
+                    "{} {}: {}",
+                    package.name,
+                    package.version,
+                    colored(license, &style, theme)
+                );
+            }
+        }
+        println!();
+        Ok(())
     }
-    if let Some(triple) = opt.filter_platform {
-        cmd.other_options(["--filter-platform".into(), triple]);
+
+    /// Collects the dependencies of every `--manifest-path`, merging crates
+    /// with the same name and version.
+    fn collect_dependencies(
+        &self,
+        opt: GetDependenciesOpt,
+    ) -> Result<(Vec<DependencyDetails>, Vec<Warning>)> {
+        if self.manifest_path.len() <= 1 {
+            let cmd = self.metadata_command(self.manifest_path.first())?;
+            return Ok(get_dependencies_with_warnings(cmd, opt)?);
+        }
+        let mut dependencies = Vec::new();
+        let mut warnings = Vec::new();
+        for path in &self.manifest_path {
+            let (path_dependencies, path_warnings) =
+                get_dependencies_with_warnings(self.metadata_command(Some(path))?, opt.clone())
+                    .with_context(|| {
+                        format!("failed to get the dependencies of `{}`", path.display())
+                    })?;
+            dependencies.extend(path_dependencies);
+            for warning in path_warnings {
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+        }
+        // Topological orders can't be merged, the reports are concatenated.
+        if self.sort_by == SortByArg::Name {
+            dependencies.sort_unstable();
+        }
+        // Unlike within a single report, crates of different reports may
+        // differ in details lost to the merge, so that is always reported.
+        warnings.extend(dedupe_dependencies(&mut dependencies));
+        Ok((dependencies, warnings))
     }
 
-    let get_opts = GetDependenciesOpt {
-        avoid_dev_deps: opt.avoid_dev_deps,
-        avoid_build_deps: opt.avoid_build_deps,
-        avoid_proc_macros: opt.avoid_proc_macros,
-        direct_deps_only: opt.direct_deps_only,
-        root_only: opt.root_only,
-    };
+    fn get_dependencies_opt(&self) -> Result<GetDependenciesOpt> {
+        let on_timing: fn(&str, Duration) = match self.log_format {
+            LogFormat::Human => |phase, elapsed| LogFormat::Human.log_timing(phase, elapsed),
+            LogFormat::Json => |phase, elapsed| LogFormat::Json.log_timing(phase, elapsed),
+        };
+        let overrides = match &self.overrides {
+            Some(path) => LicenseOverrides::from_file(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?,
+            None => LicenseOverrides::default(),
+        };
+        let tags = match &self.tags {
+            Some(path) => CrateTags::from_file(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?,
+            None => CrateTags::default(),
+        };
+        let mut exclude = self.exclude.clone();
+        if let Some(path) = &self.exclude_file {
+            exclude.extend(
+                read_exclude_file(path)
+                    .with_context(|| format!("failed to read `{}`", path.display()))?,
+            );
+        }
+        let mut builder = GetDependenciesOpt::builder()
+            .avoid_dev_deps(self.avoid_dev_deps)
+            .avoid_build_deps(self.avoid_build_deps)
+            .avoid_proc_macros(self.avoid_proc_macros)
+            .direct_deps_only(self.direct_deps_only)
+            .root_only(self.root_only)
+            .no_root(self.no_root)
+            .ignore_private(self.ignore_private)
+            .build_deps_only(self.build_deps_only)
+            .dedupe_authors(self.dedupe_authors)
+            .normalize_repos(self.normalize_repos)
+            .vendor_dir(self.vendor_dir.clone())
+            .progress(self.progress)
+            .check_yanked(self.check_yanked)
+            .since(self.since.clone())
+            .include_graph_stats(self.include_graph_stats)
+            .include_transitive_count(self.include_transitive_count)
+            .include_kinds(self.include_kinds)
+            .include_dependency_paths(self.who_uses.is_some())
+            .kinds(self.kind.iter().copied().map(DependencyKind::from))
+            .include_features(self.include_features)
+            .no_normalize_sort(self.no_normalize_sort)
+            .unique(self.unique)
+            .include_metadata(self.include_metadata)
+            .sort_by(self.sort_by.into())
+            .license_file_paths(if self.relative_paths {
+                LicenseFilePaths::WorkspaceRelative
+            } else {
+                self.license_file_paths.into()
+            })
+            .hash_license_files(self.hash_license_files)
+            .timings(self.timings)
+            .on_timing(on_timing)
+            .exclude(exclude)
+            .include_license_match(&self.include_license_match)
+            .exclude_license_match(&self.exclude_license_match)
+            .exclude_license(&self.exclude_license)
+            .exclude_workspace_members(&self.exclude_workspace_member)
+            .overrides(overrides)
+            .tags(tags);
+        if let Some(kinds) = &self.dep_kinds {
+            builder = builder.dep_kinds(kinds.iter().copied().map(DependencyKind::from));
+        }
+        if let Some(package) = &self.package {
+            builder = builder.package(package);
+        }
+        if let Some(separator) = &self.author_separator {
+            builder = builder.author_separator(separator);
+        }
+        if let Some(max_depth) = self.max_depth {
+            builder = builder.max_depth(max_depth);
+        }
+        Ok(builder.build())
+    }
 
-    let dependencies = get_dependencies_from_cargo_lock(cmd, get_opts)?;
+    /// Selects the output format from `--format`, or from
+    /// `CARGO_LICENSE_FORMAT` when no format flag was passed.
+    fn apply_format(&mut self) -> Result<()> {
+        let format = match self.format {
+            Some(format) => Some(format),
+            None => self.format_env()?,
+        };
+        match format {
+            None | Some(OutputFormat::Grouped) => {}
+            Some(OutputFormat::DoNotBundle) => self.do_not_bundle = true,
+            Some(OutputFormat::Tsv) => self.tsv = true,
+            Some(OutputFormat::Json) => self.json = true,
+            Some(OutputFormat::JsonGrouped) => self.json_grouped = true,
+            Some(OutputFormat::Toml) => self.toml = true,
+            Some(OutputFormat::Gitlab) => self.gitlab = true,
+            Some(OutputFormat::Ort) => self.ort = true,
+            Some(OutputFormat::Syft) => self.syft = true,
+        }
+        if self.json_envelope && !self.json {
+            bail!("`--json-envelope` requires the JSON output format");
+        }
+        Ok(())
+    }
 
-    let enable_color = match opt.color {
-        Color::Auto => io::stdin().is_terminal(),
-        Color::Always => true,
-        Color::Never => false,
-    };
+    /// The output format of `CARGO_LICENSE_FORMAT`, unless a format flag was
+    /// passed.
+    fn format_env(&self) -> Result<Option<OutputFormat>> {
+        let has_format = self.format_string.is_some()
+            || self.template.is_some()
+            || self.bundle_licenses
+            || self.obligations
+            || self.licenses_only
+            || self.tsv
+            || self.json
+            || self.json_grouped
+            || self.toml
+            || self.gitlab
+            || self.ort
+            || self.syft
+            || self.do_not_bundle;
+        let Some(format) = std::env::var_os("CARGO_LICENSE_FORMAT") else {
+            return Ok(None);
+        };
+        if has_format {
+            return Ok(None);
+        }
+        match format.to_string_lossy().as_ref() {
+            "" => Ok(None),
+            format => OutputFormat::from_str(format, false)
+                .map(Some)
+                .map_err(|_| {
+                    anyhow!(
+                    "unsupported `CARGO_LICENSE_FORMAT` `{format}`, expected one of grouped, tsv, \
+                     json, json-grouped, toml, gitlab, ort, syft or do-not-bundle"
+                )
+                }),
+        }
+    }
 
-    if opt.tsv {
-        write_tsv(&dependencies)?;
-    } else if opt.json {
-        write_json(&dependencies)?;
-    } else if opt.gitlab {
-        write_gitlab(&dependencies)?;
-    } else if opt.do_not_bundle {
-        one_license_per_line(dependencies, opt.authors, enable_color);
-    } else {
-        group_by_license_type(dependencies, opt.authors, enable_color);
+    /// The width to wrap the grouped output to: `--wrap`, or the terminal
+    /// width when stdout is a terminal.
+    fn wrap_width(&self) -> Option<usize> {
+        self.wrap.or_else(|| {
+            if !io::stdout().is_terminal() {
+                return None;
+            }
+            terminal_size::terminal_size()
+                .map(|(terminal_size::Width(width), _)| usize::from(width))
+        })
+    }
+
+    /// Checks the dependencies against every requested license policy.
+    fn check_policies(&self, dependencies: &[DependencyDetails]) -> Result<Vec<PolicyViolation>> {
+        type Check = fn(&[DependencyDetails], Interpretation) -> Vec<PolicyViolation>;
+        let mut violations = match &self.deny_config {
+            Some(path) => LicensePolicy::from_deny_config(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?
+                .check_with(dependencies, self.interpretation())?,
+            None => Vec::new(),
+        };
+        let checks: [(bool, Check); 4] = [
+            (self.require_election, |d, _| check_elections(d)),
+            (self.osi_only, check_osi_approved),
+            (self.fsf_only, check_fsf_libre),
+            (self.no_deprecated_licenses, |d, _| check_deprecated(d)),
+        ];
+        for (enabled, check) in checks {
+            if self.fail_fast && !violations.is_empty() {
+                break;
+            }
+            if enabled {
+                violations.extend(check(dependencies, self.interpretation()));
+            }
+        }
+        if self.fail_fast {
+            violations.truncate(1);
+        }
+        Ok(violations)
+    }
+
+    /// Gives the crates that only have a license file the license of
+    /// `--assume-license-file`, if any.
+    fn assume_license_file(
+        &self,
+        mut dependencies: Vec<DependencyDetails>,
+    ) -> Vec<DependencyDetails> {
+        if let Some(license) = &self.assume_license_file {
+            for dependency in &mut dependencies {
+                if dependency.license.is_none() && dependency.license_file.is_some() {
+                    dependency.license = Some(license.clone());
+                }
+            }
+        }
+        dependencies
+    }
+
+    fn write_output(
+        &self,
+        dependencies: Vec<DependencyDetails>,
+        warnings: &[Warning],
+        theme: Theme,
+    ) -> Result<()> {
+        let display_authors = self.authors && !self.no_authors;
+        let dependencies = if self.missing_only {
+            dependencies
+                .into_iter()
+                .filter(|d| d.license.is_none() && d.license_file.is_none())
+                .collect()
+        } else {
+            dependencies
+        };
+
+        if let Some(template) = &self.format_string {
+            write_format_string(&dependencies, template)?;
+        } else if let Some(path) = &self.template {
+            let template = fs::read_to_string(path)
+                .with_context(|| format!("failed to read `{}`", path.display()))?;
+            write_template(&template, &dependencies)?;
+        } else if self.licenses_only {
+            let licenses = dependencies
+                .iter()
+                .filter_map(|dependency| dependency.license.as_deref())
+                .collect::<BTreeSet<_>>();
+            for license in licenses {
+                println!("{license}");
+            }
+        } else if self.obligations {
+            print_obligations(&dependencies, theme);
+        } else if self.spdx_stats {
+            print_spdx_stats(&dependencies, theme);
+        } else if let Some(licensee) = &self.who_uses {
+            print_who_uses(&dependencies, licensee, self.json, theme)?;
+        } else if self.minimal_license_set {
+            print_minimal_license_set(&dependencies, theme);
+        } else if self.verdict {
+            let project_license = self.project_license()?;
+            print_verdict(&dependencies, &project_license, self.json, theme)?;
+        } else if self.count_by_category {
+            print_category_counts(&dependencies, self.interpretation(), self.json, theme)?;
+        } else if self.author_stats {
+            print_author_stats(&dependencies, self.top_authors, theme);
+        } else if self.bundle_licenses {
+            if let Some(output_dir) = &self.output_dir {
+                write_license_files(&dependencies, output_dir, self.log_format)?;
+            } else {
+                bundle_licenses(&dependencies, self.normalize_license_text, theme)?;
+            }
+        } else if self.tsv {
+            write_tsv(&dependencies)?;
+        } else if self.json_grouped {
+            write_json_grouped(dependencies)?;
+        } else if self.json_envelope {
+            write_json_envelope(&dependencies, warnings)?;
+        } else if self.json {
+            write_json(&dependencies)?;
+        } else if self.toml {
+            write_toml(&dependencies)?;
+        } else if self.gitlab {
+            write_gitlab(&dependencies)?;
+        } else if self.ort {
+            write_ort(&dependencies)?;
+        } else if self.syft {
+            write_syft(&dependencies)?;
+        } else if self.do_not_bundle {
+            one_license_per_line(dependencies, display_authors, !self.no_version, theme);
+        } else if self.missing_only {
+            for dependency in &dependencies {
+                println!(
+                    "{} {} {}",
+                    dependency.name,
+                    dependency.version,
+                    dependency.repository.as_deref().unwrap_or("N/A")
+                );
+            }
+        } else {
+            group_by_license_type(
+                dependencies,
+                display_authors,
+                self.stats,
+                theme,
+                self.wrap_width(),
+                self.interpretation(),
+                self.group_threshold,
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Notes every crate whose declared license was overridden.
+fn log_overrides(
+    dependencies: &[DependencyDetails],
+    overrides: &LicenseOverrides,
+    log_format: LogFormat,
+) {
+    for dependency in dependencies {
+        if let Some(entry) = overrides.get(&dependency.name) {
+            let message = format!(
+                "using overridden license `{}` for `{} {}`{}",
+                entry.license,
+                dependency.name,
+                dependency.version,
+                entry
+                    .reason
+                    .as_ref()
+                    .map(|reason| format!(": {reason}"))
+                    .unwrap_or_default()
+            );
+            log_format.log(
+                "note",
+                &message,
+                json!({ "crate": dependency.name, "version": dependency.version }),
+            );
+        }
+    }
+}
+
+fn run(mut opt: Opt) -> Result<()> {
+    opt.apply_format()?;
+    let theme = opt.theme();
+
+    if let Some(id) = &opt.explain {
+        return explain_license(id, theme);
+    }
+    if opt.print_schema {
+        println!("{}", json_schema_string()?);
+        return Ok(());
+    }
+
+    // The temporary project is only a wrapper, so only its dependencies are
+    // reported.
+    let remote = opt
+        .remote
+        .as_deref()
+        .map(RemoteProject::create)
+        .transpose()?;
+    if let Some(remote) = &remote {
+        opt.manifest_path = vec![remote.manifest_path()];
+        opt.no_root = true;
+    }
+    let get_opts = opt.get_dependencies_opt()?;
+    let overrides = get_opts.overrides.clone();
+    let (dependencies, mut warnings) = opt.collect_dependencies(get_opts)?;
+    if let Some(max) = opt.max_crates.filter(|&max| dependencies.len() > max) {
+        bail!(
+            "resolved {} crates, more than the {max} allowed by `--max-crates`",
+            dependencies.len()
+        );
+    }
+
+    log_overrides(&dependencies, &overrides, opt.log_format);
+
+    if opt.check_consistency {
+        warnings.extend(check_consistency(&dependencies));
+    }
+    // After the consistency check, which would flag the assumed license as
+    // declared alongside the license file.
+    let dependencies = opt.assume_license_file(dependencies);
+    if opt.check_compatibility {
+        warnings.extend(check_compatibility(&dependencies));
+    }
+    for warning in &warnings {
+        opt.log_format
+            .log("warning", &warning.message, json!({ "kind": warning.kind }));
+    }
+
+    let violations = opt.check_policies(&dependencies)?;
+
+    if let Some(path) = &opt.fail_on_change {
+        check_license_hash(path, &dependencies, opt.update_license_hash, opt.log_format)?;
+    }
+    if let Some(path) = &opt.verify {
+        let committed =
+            read_json(path).with_context(|| format!("failed to read `{}`", path.display()))?;
+        let diff = diff_dependencies(&committed, &dependencies);
+        if !diff.is_empty() {
+            print_diff(&diff, theme);
+            bail!("license report differs from `{}`", path.display());
+        }
+    } else if opt.policy_json {
+        write_policy_json(&violations)?;
+    } else if !opt.fail_fast || violations.is_empty() {
+        let start = Instant::now();
+        if opt.show_root_license {
+            opt.print_root_licenses(theme)?;
+        }
+        opt.write_output(dependencies, &warnings, theme)?;
+        if opt.timings {
+            opt.log_format.log_timing("output", start.elapsed());
+        }
+    }
+
+    if !violations.is_empty() {
+        if !opt.policy_json {
+            for violation in &violations {
+                let message = format!(
+                    "`{} {}` ({}): {}",
+                    violation.name,
+                    violation.version,
+                    violation.license.as_deref().unwrap_or("N/A"),
+                    violation.rule
+                );
+                opt.log_format
+                    .log("error", &message, serde_json::to_value(violation)?);
+            }
+        }
+        bail!("{} crate(s) violate the license policy", violations.len());
+    }
+    if opt.strict && !warnings.is_empty() {
+        bail!("{} warning(s) emitted with `--strict`", warnings.len());
     }
     Ok(())
 }
 
 fn main() {
-    exit(match run() {
+    // Drop extra `license` argument when called by `cargo`.
+    let args = std::env::args().enumerate().filter_map(|(i, x)| {
+        if (i, x.as_str()) == (1, "license") {
+            None
+        } else {
+            Some(x)
+        }
+    });
+
+    let opt = Opt::parse_from(args);
+    let log_format = opt.log_format;
+    exit(match run(opt) {
         Ok(()) => 0,
         Err(e) => {
-            for cause in e.chain() {
-                eprintln!("{cause}");
+            match log_format {
+                LogFormat::Human => {
+                    for cause in e.chain() {
+                        eprintln!("{cause}");
+                    }
+                }
+                LogFormat::Json => log_format.log("error", &format!("{e:#}"), json!({})),
             }
             1
         }
     })
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_parse_remote() {
+        for spec in [
+            "serde",
+            "serde@1.0.200",
+            "serde_json",
+            "cargo-license@0.6.1",
+        ] {
+            assert_eq!(parse_remote(spec).as_deref(), Ok(spec));
+        }
+        for spec in [
+            "",
+            "@1.0.0",
+            "serde@",
+            "serde@1",
+            "serde@^1.0",
+            "../serde",
+            "ser de",
+        ] {
+            assert!(parse_remote(spec).is_err(), "`{spec}` is invalid");
+        }
+    }
+
+    fn dependency(name: &str, license: Option<&str>) -> DependencyDetails {
+        serde_json::from_value(json!({ "name": name, "version": "1.0.0", "license": license }))
+            .unwrap()
+    }
+
+    #[test]
+    fn test_render_format_string() {
+        let dependency = dependency("serde", Some("MIT OR Apache-2.0"));
+        for (template, expected) in [
+            ("{name} {version}", "serde 1.0.0"),
+            ("{name}: {license}", "serde: MIT OR Apache-2.0"),
+            ("{{{name}}}", "{serde}"),
+            ("{{name}} }}{{", "{name} }{"),
+            ("[{repository}]", "[]"),
+            ("no placeholders", "no placeholders"),
+        ] {
+            assert_eq!(
+                render_format_string(template, &dependency).unwrap(),
+                expected,
+                "rendering `{template}`"
+            );
+        }
+        for (template, error) in [
+            ("{nam}", "unknown placeholder `{nam}` in format string"),
+            ("{}", "unknown placeholder `{}` in format string"),
+            ("{name", "unclosed `{` in format string"),
+            ("name}", "unmatched `}` in format string"),
+            ("{name}}", "unmatched `}` in format string"),
+        ] {
+            assert_eq!(
+                render_format_string(template, &dependency)
+                    .unwrap_err()
+                    .to_string(),
+                error,
+                "rendering `{template}`"
+            );
+        }
+    }
+
+    #[test]
+    fn test_wrap_list() {
+        let items = ["aa", "bb", "cc"].map(str::to_owned);
+        assert_eq!(wrap_list(&items, 0, None), "aa, bb, cc");
+        assert_eq!(wrap_list(&items, 0, Some(0)), "aa, bb, cc");
+        assert_eq!(wrap_list(&items, 0, Some(10)), "aa, bb, cc");
+        // "aa, bb," fills a line of 7 exactly.
+        assert_eq!(wrap_list(&items, 0, Some(7)), "aa, bb,\n    cc");
+        assert_eq!(wrap_list(&items, 0, Some(6)), "aa,\n    bb,\n    cc");
+        // The text before the list counts towards the first line.
+        assert_eq!(wrap_list(&items, 4, Some(10)), "aa,\n    bb, cc");
+        // Items longer than the width are never split.
+        let long = ["a-very-long-crate-name".to_owned(), "b".to_owned()];
+        assert_eq!(
+            wrap_list(&long, 10, Some(8)),
+            "a-very-long-crate-name,\n    b"
+        );
+    }
+
+    #[test]
+    fn test_collapse_small_groups() {
+        let table = group_by_license(vec![
+            dependency("a", Some("MIT")),
+            dependency("b", Some("MIT")),
+            dependency("c", Some("Zlib")),
+            dependency("d", Some("GPL-3.0-only")),
+            dependency("e", None),
+            dependency("f", Some("ISC")),
+        ]);
+        let names = |threshold| {
+            collapse_small_groups(table.clone(), Interpretation::BestCase, threshold)
+                .into_iter()
+                .map(|(license, crates)| {
+                    let names = crates.iter().map(|c| c.name.as_str()).collect::<Vec<_>>();
+                    format!("{license}: {}", names.join(", "))
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(names(None).len(), 5);
+        assert_eq!(
+            names(Some(2)),
+            ["GPL-3.0-only: d", "MIT: a, b", "N/A: e", "Other: c, f"]
+        );
+        assert_eq!(
+            names(Some(3)),
+            ["GPL-3.0-only: d", "N/A: e", "Other: a, b, c, f"]
+        );
+    }
+
+    #[test]
+    fn test_create_temp_dir() {
+        let (a, b) = (create_temp_dir().unwrap(), create_temp_dir().unwrap());
+        assert_ne!(a, b);
+        for dir in [a, b] {
+            assert!(dir.is_dir());
+            fs::remove_dir(dir).unwrap();
+        }
+    }
+}